@@ -1,31 +1,47 @@
-use tauri::{AppHandle, Runtime, Manager}; // NEW
+use tauri::{AppHandle, Emitter, Runtime, Manager, State, Window}; // NEW
 use serde::{Serialize, Deserialize};
 use std::{
   fs::File,
   io::{BufReader, Read},
   path::{Path, PathBuf},
 };
-use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{gitignore::{Gitignore, GitignoreBuilder}, Match};
 use calamine::{Reader, open_workbook_auto, DataType};
 use scraper::{Html, Selector};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64_STANDARD};
+use sha2::{Digest, Sha256};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use chardetng::EncodingDetector;
 
 // ⬇ add near the other imports at the top
 use std::fs;
 use std::fs::create_dir_all;
 // ⬇ add with the other use lines at the top if not present
 use serde_json::{Value, Map};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use notify::Watcher;
 use reqwest; // already implied by your other commands
 
 /* ====================== Data types returned to the frontend ====================== */
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct FileNode {
   name: String,
   path: String,
   is_dir: bool,
+  is_symlink: bool,
   children: Option<Vec<FileNode>>,
+  // Files only: size in bytes and modification time in ms since the Unix epoch
+  #[serde(skip_serializing_if = "Option::is_none")]
+  size: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  mtime: Option<u64>,
+  // Set only when `path` was made root-relative (scan_dir relativePaths)
+  #[serde(skip_serializing_if = "Option::is_none")]
+  abs_path: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -33,18 +49,106 @@ struct FileNode {
 struct FileValue {
   file_path: String,
   value: String,
+  language: String,
+  chars: usize,
+  lines: usize,
+  words: usize,
 }
 
-#[derive(Serialize)]
+impl FileValue {
+  // Derives language and size stats from the path and produced text
+  fn new(file_path: String, value: String) -> Self {
+    let language = language_for_path(Path::new(&file_path)).to_string();
+    let chars = value.chars().count();
+    let lines = if value.is_empty() { 0 } else { value.matches('\n').count() + 1 };
+    let words = value.split_whitespace().count();
+    FileValue { file_path, value, language, chars, lines, words }
+  }
+}
+
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ApiTable {
   columns: Vec<String>,
   // each row is a flat map of column -> stringified value
   rows: Vec<HashMap<String, String>>,
+  // The untouched API response, when the caller asked for it (fetch_api_table includeRaw)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  raw: Option<Value>,
+}
+
+/* ====================== Errors returned to the frontend ====================== */
+
+// Serialized as { kind, message } (NotFound carries no message) so the UI can
+// tell a missing file from a network failure or a bad config.
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", content = "message")]
+enum CommandError {
+  NotFound,
+  Io(String),
+  Network(String),
+  Parse(String),
+  Config(String),
+}
+
+impl std::fmt::Display for CommandError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CommandError::NotFound => write!(f, "Not found"),
+      CommandError::Io(m) => write!(f, "I/O error: {}", m),
+      CommandError::Network(m) => write!(f, "Network error: {}", m),
+      CommandError::Parse(m) => write!(f, "Parse error: {}", m),
+      CommandError::Config(m) => write!(f, "Config error: {}", m),
+    }
+  }
+}
+
+impl CommandError {
+  // I/O failure on a known path; missing files become NotFound
+  fn io_at(path: impl std::fmt::Display, e: std::io::Error) -> Self {
+    if e.kind() == std::io::ErrorKind::NotFound {
+      CommandError::NotFound
+    } else {
+      CommandError::Io(format!("{}: {}", path, e))
+    }
+  }
+}
+
+impl From<std::io::Error> for CommandError {
+  fn from(e: std::io::Error) -> Self {
+    if e.kind() == std::io::ErrorKind::NotFound { CommandError::NotFound } else { CommandError::Io(e.to_string()) }
+  }
+}
+
+impl From<reqwest::Error> for CommandError {
+  fn from(e: reqwest::Error) -> Self { CommandError::Network(e.to_string()) }
+}
+
+impl From<calamine::Error> for CommandError {
+  fn from(e: calamine::Error) -> Self {
+    match e {
+      calamine::Error::Io(io) => io.into(),
+      other => CommandError::Parse(other.to_string()),
+    }
+  }
+}
+
+impl From<regex::Error> for CommandError {
+  fn from(e: regex::Error) -> Self { CommandError::Config(e.to_string()) }
+}
+
+impl From<notify::Error> for CommandError {
+  fn from(e: notify::Error) -> Self { CommandError::Io(e.to_string()) }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for CommandError {
+  fn from(e: std::sync::PoisonError<T>) -> Self { CommandError::Io(e.to_string()) }
 }
 
 const SYSTEM_PROMPT_FILENAME: &str = "rapid-prompt-system-prompt.txt";
 
+const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/127 Safari/537.36";
+
 /* ====================== .gitignore support (root only) ====================== */
 
 const HIDDEN_DIR_NAMES: &[&str] = &[".git" /*, ".svn", ".hg" */];
@@ -53,7 +157,16 @@ fn is_hidden_dir(name: &str) -> bool {
   HIDDEN_DIR_NAMES.iter().any(|&n| n == name)
 }
 
-fn load_root_gitignore(root: &Path) -> Option<Gitignore> {
+// Root .gitignore matcher plus the literal path prefixes of its anchored `!` patterns.
+// Git never re-includes below an excluded directory, but users expect
+// `node_modules/` + `!node_modules/keep-me/` to show keep-me, so we descend into
+// an ignored directory only when one of these prefixes points beneath it.
+struct RootIgnore {
+  matcher: Gitignore,
+  negated_prefixes: Vec<String>,
+}
+
+fn load_root_gitignore(root: &Path) -> Option<RootIgnore> {
   let gi_path = root.join(".gitignore");
   if !gi_path.is_file() {
     return None;
@@ -67,33 +180,121 @@ fn load_root_gitignore(root: &Path) -> Option<Gitignore> {
     return None;
   }
 
-  builder.build().ok()
+  let matcher = builder.build().ok()?;
+  let negated_prefixes = fs::read_to_string(&gi_path)
+    .map(|text| text.lines().filter_map(negated_prefix).collect())
+    .unwrap_or_default();
+
+  Some(RootIgnore { matcher, negated_prefixes })
+}
+
+// "!/node_modules/keep-me/" -> "node_modules/keep-me"; unanchored negations yield None
+fn negated_prefix(line: &str) -> Option<String> {
+  let pat = line.trim().strip_prefix('!')?;
+  let pat = pat.trim_start_matches('/').trim_end_matches('/');
+  if !pat.contains('/') {
+    return None;
+  }
+  let literal_end = pat.find(['*', '?', '[']).unwrap_or(pat.len());
+  let literal = pat[..literal_end].trim_end_matches('/');
+  if literal.is_empty() { None } else { Some(literal.to_string()) }
+}
+
+fn rel_slash_path(root: &Path, candidate: &Path) -> String {
+  let rel = candidate.strip_prefix(root).unwrap_or(candidate);
+  rel.to_string_lossy().replace('\\', "/")
 }
 
-// Prefer matching against a path relative to the chosen root.
-fn is_ignored(root: &Path, gi: Option<&Gitignore>, candidate: &Path, is_dir: bool) -> bool {
-  if let Some(matcher) = gi {
+// Nearest matching rule wins (so `!` re-includes work); unmatched paths inherit
+// the ignored state of their parent directory.
+fn is_ignored(root: &Path, gi: Option<&RootIgnore>, candidate: &Path, is_dir: bool, parent_ignored: bool) -> bool {
+  if let Some(rules) = gi {
     let rel = candidate.strip_prefix(root).unwrap_or(candidate);
-    return matcher.matched_path_or_any_parents(rel, is_dir).is_ignore();
+    match rules.matcher.matched(rel, is_dir) {
+      Match::Ignore(_) => return true,
+      Match::Whitelist(_) => return false,
+      Match::None => {}
+    }
   }
-  false
+  parent_ignored
+}
+
+// Whether an ignored directory may still contain re-included paths
+fn may_contain_negated(root: &Path, gi: Option<&RootIgnore>, dir: &Path) -> bool {
+  let Some(rules) = gi else { return false; };
+  let rel = rel_slash_path(root, dir);
+  rules.negated_prefixes.iter().any(|p| p.starts_with(&format!("{}/", rel)))
 }
 
 /* ====================== Tree building (with .gitignore filtering) ====================== */
 
-fn build_tree_rec(root: &Path, dir: &Path, gi: Option<&Gitignore>) -> std::io::Result<FileNode> {
+// Caller-selected scan behavior; also part of the scan cache key
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+struct ScanOptions {
+  follow_symlinks: bool,
+  // Keep only files whose root-relative path ("src/main.rs") matches
+  path_regex: Option<String>,
+  sort_by: SortBy,
+}
+
+// File order within a directory: name (A-Z), mtime (newest first) or size (largest first)
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+enum SortBy {
+  #[default]
+  Name,
+  Mtime,
+  Size,
+}
+
+impl SortBy {
+  fn parse(value: Option<&str>) -> Result<Self, CommandError> {
+    match value.map(str::trim).unwrap_or("name") {
+      "" | "name" => Ok(SortBy::Name),
+      "mtime" => Ok(SortBy::Mtime),
+      "size" => Ok(SortBy::Size),
+      other => Err(CommandError::Config(format!("Unknown sortBy: {} (expected name, mtime or size)", other))),
+    }
+  }
+}
+
+// State shared across one tree walk
+struct TreeWalk<'a> {
+  root: &'a Path,
+  gi: Option<&'a RootIgnore>,
+  opts: &'a ScanOptions,
+  path_re: Option<regex::Regex>,
+  // Canonical directories already expanded; guards against symlink cycles
+  visited: HashSet<PathBuf>,
+}
+
+impl TreeWalk<'_> {
+  fn path_matches(&self, p: &Path) -> bool {
+    self.path_re.as_ref().is_none_or(|re| re.is_match(&rel_slash_path(self.root, p)))
+  }
+}
+
+fn symlink_leaf(p: &Path) -> FileNode {
+  FileNode {
+    name: p.file_name().unwrap_or_default().to_string_lossy().to_string(),
+    path: p.to_string_lossy().to_string(),
+    is_dir: false,
+    is_symlink: true,
+    children: None,
+    size: None,
+    mtime: None,
+    abs_path: None,
+  }
+}
+
+fn build_tree_rec(walk: &mut TreeWalk, dir: &Path, dir_ignored: bool) -> std::io::Result<FileNode> {
   let name = dir.file_name()
     .map(|s| s.to_string_lossy().to_string())
     .unwrap_or_else(|| dir.to_string_lossy().to_string());
 
-  // If this directory (not the root) is ignored, return an empty dir node (caller keeps/skips)
-  if dir != root && is_ignored(root, gi, dir, true) {
-    return Ok(FileNode {
-      name,
-      path: dir.to_string_lossy().to_string(),
-      is_dir: true,
-      children: Some(vec![]),
-    });
+  if walk.opts.follow_symlinks {
+    if let Ok(canon) = dir.canonicalize() {
+      walk.visited.insert(canon);
+    }
   }
 
   let mut children: Vec<FileNode> = Vec::new();
@@ -105,10 +306,27 @@ fn build_tree_rec(root: &Path, dir: &Path, gi: Option<&Gitignore>) -> std::io::R
     };
     let p = ent.path();
 
-    let md = match ent.metadata() {
+    // symlink_metadata does not follow links, so we can spot them
+    let lmd = match p.symlink_metadata() {
       Ok(m) => m,
       Err(_) => continue,
     };
+    let is_symlink = lmd.file_type().is_symlink();
+
+    let md = if is_symlink && walk.opts.follow_symlinks {
+      match fs::metadata(&p) {
+        Ok(m) => m,
+        Err(_) => {
+          // Dangling link: keep it visible as a leaf
+          if !is_ignored(walk.root, walk.gi, &p, false, dir_ignored) && walk.path_matches(&p) {
+            children.push(symlink_leaf(&p));
+          }
+          continue;
+        }
+      }
+    } else {
+      lmd
+    };
     let is_dir = md.is_dir();
 
     let fname = ent.file_name();
@@ -120,29 +338,70 @@ fn build_tree_rec(root: &Path, dir: &Path, gi: Option<&Gitignore>) -> std::io::R
     }
 
     // Apply root .gitignore rules
-    if is_ignored(root, gi, &p, is_dir) {
+    let ignored = is_ignored(walk.root, walk.gi, &p, is_dir, dir_ignored);
+
+    if is_symlink && !walk.opts.follow_symlinks {
+      if !ignored && walk.path_matches(&p) {
+        children.push(symlink_leaf(&p));
+      }
       continue;
     }
 
     if is_dir {
-      let node = build_tree_rec(root, &p, gi)?;
+      if ignored && !may_contain_negated(walk.root, walk.gi, &p) {
+        continue;
+      }
+      // A followed link back into an already expanded directory stays a leaf
+      if is_symlink && p.canonicalize().map_or(true, |c| walk.visited.contains(&c)) {
+        if !ignored && walk.path_matches(&p) {
+          children.push(symlink_leaf(&p));
+        }
+        continue;
+      }
+      let mut node = build_tree_rec(walk, &p, ignored)?;
+      node.is_symlink = is_symlink;
+      // An ignored directory only shows up to hold its re-included descendants,
+      // and with a path filter, empty directories are pruned
+      let empty = node.children.as_deref().unwrap_or_default().is_empty();
+      if empty && (ignored || walk.path_re.is_some()) {
+        continue;
+      }
       children.push(node);
     } else {
+      if ignored {
+        continue;
+      }
+      if !walk.path_matches(&p) {
+        continue;
+      }
+      let mtime = md.modified().ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64);
       children.push(FileNode {
         name: p.file_name().unwrap_or_default().to_string_lossy().to_string(),
         path: p.to_string_lossy().to_string(),
         is_dir: false,
+        is_symlink,
         children: None,
+        size: Some(md.len()),
+        mtime,
+        abs_path: None,
       });
     }
   }
 
-  // Sort: dirs first, then files, by name (case-insensitive)
+  // Sort: dirs first (by name), then files by the requested key; ties by name (case-insensitive)
   children.sort_by(|a, b| {
+    let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
     match (a.is_dir, b.is_dir) {
       (true, false) => std::cmp::Ordering::Less,
       (false, true) => std::cmp::Ordering::Greater,
-      _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+      (true, true) => by_name(),
+      (false, false) => match walk.opts.sort_by {
+        SortBy::Name => by_name(),
+        SortBy::Mtime => b.mtime.cmp(&a.mtime).then_with(by_name),
+        SortBy::Size => b.size.cmp(&a.size).then_with(by_name),
+      },
     }
   });
 
@@ -150,13 +409,77 @@ fn build_tree_rec(root: &Path, dir: &Path, gi: Option<&Gitignore>) -> std::io::R
     name,
     path: dir.to_string_lossy().to_string(),
     is_dir: true,
+    is_symlink: false,
     children: Some(children),
+    size: None,
+    mtime: None,
+    abs_path: None,
   })
 }
 
-fn build_tree_with_gitignore(root: &Path) -> std::io::Result<FileNode> {
+fn build_tree_with_gitignore(root: &Path, opts: &ScanOptions, path_re: Option<regex::Regex>) -> std::io::Result<FileNode> {
   let gi = load_root_gitignore(root);
-  build_tree_rec(root, root, gi.as_ref())
+  let mut walk = TreeWalk {
+    root,
+    gi: gi.as_ref(),
+    opts,
+    path_re,
+    visited: HashSet::new(),
+  };
+  build_tree_rec(&mut walk, root, false)
+}
+
+/* ====================== Scan summary (counts only, no FileNodes) ====================== */
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ScanSummary {
+  file_count: usize,
+  dir_count: usize,
+  total_bytes: u64,
+}
+
+// Same visibility rules as build_tree_rec (symlinks are not followed and count
+// as files). Returns whether anything visible was found under `dir`.
+fn summarize_rec(root: &Path, dir: &Path, gi: Option<&RootIgnore>, dir_ignored: bool, sum: &mut ScanSummary) -> std::io::Result<bool> {
+  let mut any_visible = false;
+
+  for entry in std::fs::read_dir(dir)? {
+    let ent = match entry {
+      Ok(e) => e,
+      Err(_) => continue,
+    };
+    let p = ent.path();
+    let md = match p.symlink_metadata() {
+      Ok(m) => m,
+      Err(_) => continue,
+    };
+    let is_dir = md.is_dir();
+
+    if is_dir && is_hidden_dir(&ent.file_name().to_string_lossy()) {
+      continue;
+    }
+
+    let ignored = is_ignored(root, gi, &p, is_dir, dir_ignored);
+
+    if is_dir {
+      if ignored && !may_contain_negated(root, gi, &p) {
+        continue;
+      }
+      let has_children = summarize_rec(root, &p, gi, ignored, sum)?;
+      if ignored && !has_children {
+        continue;
+      }
+      sum.dir_count += 1;
+      any_visible = true;
+    } else if !ignored {
+      sum.file_count += 1;
+      sum.total_bytes += md.len();
+      any_visible = true;
+    }
+  }
+
+  Ok(any_visible)
 }
 
 /* ====================== ASCII-only file read (for selection content) ====================== */
@@ -175,663 +498,6850 @@ fn ascii_only_string(mut reader: impl Read, max_bytes: usize) -> std::io::Result
   Ok(out)
 }
 
-/* ====================== Tauri commands ====================== */
+/* ====================== Text decoding (BOM / charset detection) ====================== */
 
-#[tauri::command]
-fn scan_dir(path: String) -> Result<FileNode, String> {
-  let p = PathBuf::from(&path);
-  if !p.exists() {
-    return Err(format!("Path does not exist: {}", path));
+// BOM first, then the UTF-8 fast path, then a UTF-16 NUL-byte sniff, then chardetng
+fn decode_text(bytes: &[u8]) -> String {
+  if let Some((enc, bom_len)) = Encoding::for_bom(bytes) {
+    return enc.decode_without_bom_handling(&bytes[bom_len..]).0.into_owned();
   }
-  build_tree_with_gitignore(&p).map_err(|e| e.to_string())
-}
-
-#[tauri::command]
-fn read_ascii_files(paths: Vec<String>, max_bytes: Option<usize>) -> Result<Vec<FileValue>, String> {
-  let max = max_bytes.unwrap_or(512 * 1024);
-  let mut out = Vec::with_capacity(paths.len());
-  for p in paths {
-    let pb = PathBuf::from(&p);
-    if pb.is_file() {
-      let f = File::open(&pb).map_err(|e| format!("{}: {}", p, e))?;
-      let reader = BufReader::new(f);
-      let text = ascii_only_string(reader, max).map_err(|e| e.to_string())?;
-      out.push(FileValue { file_path: p, value: text });
-    }
+  if let Ok(s) = std::str::from_utf8(bytes) {
+    return s.to_string();
   }
-  Ok(out)
+  if let Some(enc) = sniff_utf16(bytes) {
+    return enc.decode_without_bom_handling(bytes).0.into_owned();
+  }
+  let mut detector = EncodingDetector::new();
+  detector.feed(bytes, true);
+  detector.guess(None, true).decode_without_bom_handling(bytes).0.into_owned()
 }
 
-#[tauri::command]
-fn load_system_prompt(app: AppHandle) -> Result<String, String> {
-  let config_dir = app
-    .path()
-    .app_config_dir()
-    .map_err(|e| e.to_string())?;
-  let file_path = config_dir.join(SYSTEM_PROMPT_FILENAME);
-
-  if !file_path.exists() {
-    return Ok(String::new());
-  }
+// BOM-less UTF-16 of mostly-ASCII text has a NUL in every other byte
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+  let sample = &bytes[..bytes.len().min(4096) & !1];
+  if sample.len() < 4 { return None; }
+  let pairs = sample.len() / 2;
+  let even_nuls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+  let odd_nuls = sample.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+  if odd_nuls * 10 >= pairs * 7 && even_nuls * 10 < pairs { return Some(UTF_16LE); }
+  if even_nuls * 10 >= pairs * 7 && odd_nuls * 10 < pairs { return Some(UTF_16BE); }
+  None
+}
 
-  let contents = fs::read_to_string(&file_path).map_err(|e| e.to_string())?;
-  Ok(contents)
+// 1-based inclusive line range; out-of-range bounds clamp to the file.
+fn select_line_range(text: &str, start_line: usize, end_line: usize) -> String {
+  let lines: Vec<&str> = text.lines().collect();
+  if lines.is_empty() { return String::new(); }
+  let start = start_line.max(1);
+  let end = end_line.min(lines.len());
+  if start > end { return String::new(); }
+  lines[start - 1..end].join("\n")
 }
 
-#[tauri::command]
-fn save_system_prompt(app: AppHandle, value: String) -> Result<(), String> {
-  let config_dir = app
-    .path()
-    .app_config_dir()
-    .map_err(|e| e.to_string())?;
+/* ====================== Language detection (fenced-block tags) ====================== */
 
-  if !config_dir.exists() {
-    create_dir_all(&config_dir).map_err(|e| e.to_string())?;
+fn language_for_path(path: &Path) -> &'static str {
+  let ext = path.extension()
+    .map(|e| e.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+  match ext.as_str() {
+    "rs" => "rust",
+    "py" => "python",
+    "ts" | "mts" | "cts" => "typescript",
+    "tsx" => "tsx",
+    "js" | "mjs" | "cjs" => "javascript",
+    "jsx" => "jsx",
+    "md" | "markdown" => "markdown",
+    "json" => "json",
+    "toml" => "toml",
+    "yaml" | "yml" => "yaml",
+    "html" | "htm" => "html",
+    "css" => "css",
+    "scss" => "scss",
+    "sql" => "sql",
+    "sh" | "bash" => "bash",
+    "ps1" => "powershell",
+    "go" => "go",
+    "java" => "java",
+    "kt" | "kts" => "kotlin",
+    "c" | "h" => "c",
+    "cpp" | "cc" | "cxx" | "hpp" | "hh" => "cpp",
+    "cs" => "csharp",
+    "rb" => "ruby",
+    "php" => "php",
+    "swift" => "swift",
+    "xml" => "xml",
+    _ => "text",
   }
-
-  let file_path = config_dir.join(SYSTEM_PROMPT_FILENAME);
-  fs::write(&file_path, value).map_err(|e| e.to_string())?;
-  Ok(())
 }
 
-/* ====================== Entry point wired for main.rs ====================== */
-
-pub fn run() {
-  tauri::Builder::default()
-    // plugins you use on the frontend
-    .plugin(tauri_plugin_dialog::init())
-    .plugin(tauri_plugin_clipboard_manager::init())
-    .plugin(tauri_plugin_fs::init())
-    // register commands
-    .invoke_handler(tauri::generate_handler![
-      scan_dir,
-      read_ascii_files,
-      inspect_excel,
-      extract_excel_units,
-      extract_regex_blocks,
-      extract_html_blocks,
-      extract_api_units,            // <— add this line
-      fetch_api_table,            // <-- add this
-      fetch_api_table_from_url,
-      load_system_prompt,       // NEW
-      save_system_prompt        // NEW
-    ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+// Best-effort MIME guess for binary payloads
+fn mime_for_path(path: &Path) -> &'static str {
+  let ext = path.extension()
+    .map(|e| e.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+  match ext.as_str() {
+    "png" => "image/png",
+    "jpg" | "jpeg" => "image/jpeg",
+    "gif" => "image/gif",
+    "webp" => "image/webp",
+    "svg" => "image/svg+xml",
+    "bmp" => "image/bmp",
+    "pdf" => "application/pdf",
+    "json" => "application/json",
+    "zip" => "application/zip",
+    "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "csv" => "text/csv",
+    "html" | "htm" => "text/html",
+    "txt" | "md" => "text/plain",
+    _ => "application/octet-stream",
+  }
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct PromptUnit {
-  id: String,
-  body: String,
-  #[serde(skip_serializing_if = "Option::is_none")]
-  meta: Option<serde_json::Value>,
-}
+/* ====================== Tauri commands ====================== */
 
-/* ---------- Excel inspector ---------- */
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ExcelSheetInfo {
-  name: String,
-  columns: Vec<String>,
-}
-#[derive(Serialize)]
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct ExcelInspector {
+struct LineSelection {
   path: String,
-  sheets: Vec<ExcelSheetInfo>,
+  start_line: usize,
+  end_line: usize,
+}
+
+// Trees from previous scans, reused while the root directory's mtime is unchanged.
+// Only direct children of the root bump that mtime, so deeper edits need
+// `clear_scan_cache` (or a rescan after touching the root) to show up.
+#[derive(Default)]
+struct ScanCache(Mutex<HashMap<(PathBuf, ScanOptions), (SystemTime, FileNode)>>);
+
+// The filters shared by scan_dir and list_files, plus the compiled path regex
+fn scan_options(
+  follow_symlinks: Option<bool>,
+  path_regex: Option<String>,
+  sort_by: Option<String>,
+) -> Result<(ScanOptions, Option<regex::Regex>), CommandError> {
+  let path_regex = path_regex.filter(|r| !r.trim().is_empty());
+  let path_re = match &path_regex {
+    Some(r) => Some(build_regex(r, None)?),
+    None => None,
+  };
+  let opts = ScanOptions {
+    follow_symlinks: follow_symlinks.unwrap_or(false),
+    path_regex,
+    sort_by: SortBy::parse(sort_by.as_deref())?,
+  };
+  Ok((opts, path_re))
 }
 
 #[tauri::command]
-fn inspect_excel(path: String) -> Result<ExcelInspector, String> {
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn scan_dir(
+  cache: State<'_, ScanCache>,
+  path: String,
+  follow_symlinks: Option<bool>,
+  path_regex: Option<String>,
+  sort_by: Option<String>,
+  relative_paths: Option<bool>,
+) -> Result<FileNode, CommandError> {
   let p = PathBuf::from(&path);
-  if !p.exists() { return Err("File not found".into()); }
-  let mut wb = open_workbook_auto(&p).map_err(|e| e.to_string())?;
-
-  let mut sheets: Vec<ExcelSheetInfo> = Vec::new();
+  let relative = relative_paths.unwrap_or(false);
+  if !p.exists() {
+    return Err(CommandError::NotFound);
+  }
+  let (opts, path_re) = scan_options(follow_symlinks, path_regex, sort_by)?;
+  let key = (p.clone(), opts.clone());
+  let mtime = fs::metadata(&p).and_then(|m| m.modified()).ok();
 
-  for sname in wb.sheet_names().to_owned() {
-    if let Some(Ok(range)) = wb.worksheet_range(&sname) {
-      // Find header row (first non-empty row)
-      let mut header: Vec<String> = Vec::new();
-      'rows: for row in range.rows() {
-        if row.iter().any(|c| !c.is_empty()) {
-          header = row.iter().enumerate().map(|(i, c)| {
-            match c {
-              DataType::String(s) => if s.trim().is_empty() { format!("col{}", i+1) } else { s.trim().to_string() },
-              DataType::Float(f) => format!("{}", f),
-              DataType::Int(i) => format!("{}", i),
-              DataType::Bool(b) => format!("{}", b),
-              _ => format!("col{}", i+1),
-            }
-          }).collect();
-          break 'rows;
-        }
-      }
-      if header.is_empty() {
-        // Fallback: number columns based on first row length
-        if let Some(first) = range.rows().next() {
-          header = (0..first.len()).map(|i| format!("col{}", i+1)).collect();
-        }
+  if let Some(mtime) = mtime {
+    let entries = cache.0.lock()?;
+    if let Some((cached_at, tree)) = entries.get(&key) {
+      if *cached_at == mtime {
+        tracing::debug!("scan cache hit");
+        let mut tree = tree.clone();
+        if relative { relativize_tree(&mut tree, &p); }
+        return Ok(tree);
       }
-      sheets.push(ExcelSheetInfo { name: sname.to_string(), columns: header });
     }
   }
 
-  Ok(ExcelInspector { path, sheets })
+  let mut tree = build_tree_with_gitignore(&p, &opts, path_re).map_err(|e| CommandError::io_at(&path, e))?;
+  if let Some(mtime) = mtime {
+    let mut entries = cache.0.lock()?;
+    entries.insert(key, (mtime, tree.clone()));
+  }
+  if relative { relativize_tree(&mut tree, &p); }
+  Ok(tree)
 }
 
-/* ---------- Excel units ---------- */
-#[derive(Deserialize)]
+// Root-relative, '/'-separated `path` ("." for the root itself); the absolute
+// path moves to `abs_path` so the backend can still reopen files. The cache
+// keeps absolute trees, so this runs on the copy handed to the UI.
+fn relativize_tree(node: &mut FileNode, root: &Path) {
+  let rel = rel_slash_path(root, Path::new(&node.path));
+  let rel = if rel.is_empty() { ".".to_string() } else { rel };
+  node.abs_path = Some(std::mem::replace(&mut node.path, rel));
+  for c in node.children.iter_mut().flatten() {
+    relativize_tree(c, root);
+  }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn list_files(
+  path: String,
+  follow_symlinks: Option<bool>,
+  path_regex: Option<String>,
+  sort_by: Option<String>,
+) -> Result<Vec<String>, CommandError> {
+  let p = PathBuf::from(&path);
+  if !p.exists() {
+    return Err(CommandError::NotFound);
+  }
+  let (opts, path_re) = scan_options(follow_symlinks, path_regex, sort_by)?;
+  let tree = build_tree_with_gitignore(&p, &opts, path_re).map_err(|e| CommandError::io_at(&path, e))?;
+  let mut files = Vec::new();
+  collect_file_paths(&tree, &mut files);
+  Ok(files)
+}
+
+#[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-struct ExcelConfig {
-  sheet: String,
-  id_column: String,
-  description_columns: Vec<String>,
+struct ScanDiff {
+  added: Vec<String>,
+  removed: Vec<String>,
+  common: usize,
 }
 
+// Compares the file paths of two scan_dir results (directories themselves are ignored)
 #[tauri::command]
-fn extract_excel_units(path: String, config: ExcelConfig) -> Result<Vec<PromptUnit>, String> {
-  let mut wb = open_workbook_auto(&path).map_err(|e| e.to_string())?;
-  let range = wb.worksheet_range(&config.sheet)
-    .ok_or_else(|| format!("Sheet not found: {}", config.sheet))?
-    .map_err(|e| e.to_string())?;
+fn diff_scans(old: FileNode, new: FileNode) -> Result<ScanDiff, CommandError> {
+  let (mut old_files, mut new_files) = (Vec::new(), Vec::new());
+  collect_file_paths(&old, &mut old_files);
+  collect_file_paths(&new, &mut new_files);
+  let old_set: HashSet<&String> = old_files.iter().collect();
+  let new_set: HashSet<&String> = new_files.iter().collect();
 
-  // Find header row
-  let mut header_idx: usize = 0;
-  let mut header: Vec<String> = Vec::new();
-  for (i, row) in range.rows().enumerate() {
-    if row.iter().any(|c| !c.is_empty()) {
-      header = row.iter().enumerate().map(|(j, c)| cell_to_string(c).unwrap_or_else(|| format!("col{}", j+1))).collect();
-      header_idx = i;
-      break;
-    }
-  }
-  if header.is_empty() {
-    return Err("Could not detect header row".into());
+  Ok(ScanDiff {
+    added: new_files.iter().filter(|p| !old_set.contains(p)).cloned().collect(),
+    removed: old_files.iter().filter(|p| !new_set.contains(p)).cloned().collect(),
+    common: new_files.iter().filter(|p| old_set.contains(p)).count(),
+  })
+}
+
+#[tauri::command]
+fn scan_summary(path: String, respect_gitignore: Option<bool>) -> Result<ScanSummary, CommandError> {
+  let p = PathBuf::from(&path);
+  if !p.is_dir() {
+    return Err(CommandError::NotFound);
   }
+  let gi = if respect_gitignore.unwrap_or(true) { load_root_gitignore(&p) } else { None };
+  let mut sum = ScanSummary::default();
+  summarize_rec(&p, &p, gi.as_ref(), false, &mut sum).map_err(|e| CommandError::io_at(&path, e))?;
+  Ok(sum)
+}
 
-  let id_idx = header.iter().position(|h| h.eq_ignore_ascii_case(&config.id_column))
-    .ok_or_else(|| format!("ID column not found: {}", config.id_column))?;
+#[tauri::command]
+fn clear_scan_cache(cache: State<'_, ScanCache>) -> Result<(), CommandError> {
+  cache.0.lock()?.clear();
+  Ok(())
+}
 
-  let desc_indices: Vec<usize> = config.description_columns.iter()
-    .map(|name| header.iter().position(|h| h.eq_ignore_ascii_case(name))
-      .ok_or_else(|| format!("Description column not found: {}", name)))
-    .collect::<Result<_,_>>()?;
+/* ---------- Directory watching ---------- */
 
-  let mut units: Vec<PromptUnit> = Vec::new();
+// One live watcher per watched root; dropping it ends the debounce thread
+#[derive(Default)]
+struct DirWatchers(Mutex<HashMap<PathBuf, notify::RecommendedWatcher>>);
 
-  for (i, row) in range.rows().enumerate() {
-    if i <= header_idx { continue; }
-    let id = row.get(id_idx).and_then(cell_to_string).unwrap_or_default().trim().to_string();
-    if id.is_empty() { continue; }
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
 
-    let mut parts: Vec<String> = Vec::new();
-    for &di in desc_indices.iter() {
-      if let Some(s) = row.get(di).and_then(cell_to_string) {
-        let v = s.trim();
-        if !v.is_empty() { parts.push(v.to_string()); }
-      }
-    }
-    let body = parts.join("\n");
-    if body.is_empty() { continue; }
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DirChanged {
+  root: String,
+  paths: Vec<String>,
+}
 
-    units.push(PromptUnit {
-      id,
-      body,
-      meta: Some(serde_json::json!({
-        "sheet": config.sheet,
-        "rowIndex": i
-      })),
-    });
+// Changes under .git, or ignored by the root .gitignore as scan_dir would see them
+// (re-included `!` paths under an ignored directory included), never reach the UI.
+// Paths outside `root` are dropped rather than matched against the root's rules.
+fn watch_path_ignored(root: &Path, gi: Option<&RootIgnore>, p: &Path) -> bool {
+  let Ok(rel) = p.strip_prefix(root) else { return true; };
+  let parts: Vec<_> = rel.components().collect();
+  let mut current = root.to_path_buf();
+  let mut ignored = false;
+  for (i, part) in parts.iter().enumerate() {
+    if is_hidden_dir(&part.as_os_str().to_string_lossy()) {
+      return true;
+    }
+    current.push(part);
+    // Every component but the last is a directory; the last may already be deleted
+    let is_dir = i + 1 < parts.len() || current.is_dir();
+    ignored = is_ignored(root, gi, &current, is_dir, ignored);
   }
-
-  Ok(units)
+  ignored
 }
 
-fn cell_to_string(c: &DataType) -> Option<String> {
-  match c {
-    DataType::String(s) => Some(s.to_string()),
-    DataType::Float(f) => Some(if f.fract() == 0.0 { format!("{:.0}", f) } else { f.to_string() }),
-    DataType::Int(i) => Some(i.to_string()),
-    DataType::Bool(b) => Some(b.to_string()),
-    DataType::Empty => None,
-    _ => Some(c.to_string()),
+// Blocks for the next change, then keeps collecting until `quiet` passes with
+// no further change; None once the sending side (the watcher) is gone
+fn next_change_batch(rx: &mpsc::Receiver<PathBuf>, quiet: Duration) -> Option<Vec<String>> {
+  let first = rx.recv().ok()?;
+  let mut paths: BTreeSet<String> = BTreeSet::new();
+  paths.insert(first.to_string_lossy().to_string());
+  while let Ok(p) = rx.recv_timeout(quiet) {
+    paths.insert(p.to_string_lossy().to_string());
   }
+  Some(paths.into_iter().collect())
 }
 
-/* ---------- Regex blocks ---------- */
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct RegexConfig {
-  delimiter: String,
-  id_capture: Option<String>,
-  flags: Option<String>,
+// Emits `dir-changed` { root, paths } after each burst of create/modify/remove
+// events and drops the root's cached trees so the UI's rescan sees the change
+#[tauri::command]
+fn watch_dir(window: Window, watchers: State<'_, DirWatchers>, path: String) -> Result<(), CommandError> {
+  if !Path::new(&path).is_dir() {
+    return Err(CommandError::NotFound);
+  }
+  // Watchers report resolved paths (e.g. /private/var on macOS), so match against the resolved root
+  let root = fs::canonicalize(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let mut watchers = watchers.0.lock()?;
+  if watchers.contains_key(&root) {
+    return Ok(());
+  }
+
+  let (tx, rx) = mpsc::channel::<PathBuf>();
+  let gi = load_root_gitignore(&root);
+  let filter_root = root.clone();
+  let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+    let Ok(event) = res else { return; };
+    if event.kind.is_access() { return; }
+    for p in event.paths {
+      if !watch_path_ignored(&filter_root, gi.as_ref(), &p) {
+        let _ = tx.send(p);
+      }
+    }
+  })?;
+  watcher.watch(&root, notify::RecursiveMode::Recursive)?;
+
+  let event_root = root.clone();
+  std::thread::spawn(move || {
+    while let Some(paths) = next_change_batch(&rx, WATCH_DEBOUNCE) {
+      if let Ok(mut cache) = window.state::<ScanCache>().0.lock() {
+        cache.retain(|(cached_root, _), _| fs::canonicalize(cached_root).map_or(true, |r| r != event_root));
+      }
+      let payload = DirChanged { root: path.clone(), paths };
+      if window.emit("dir-changed", payload).is_err() { break; }
+    }
+  });
+
+  watchers.insert(root, watcher);
+  Ok(())
 }
 
 #[tauri::command]
-fn extract_regex_blocks(path: String, config: RegexConfig) -> Result<Vec<PromptUnit>, String> {
-  let data = std::fs::read(&path).map_err(|e| e.to_string())?;
-  let text = String::from_utf8_lossy(&data).into_owned();
+fn unwatch_dir(watchers: State<'_, DirWatchers>, path: String) -> Result<(), CommandError> {
+  let root = fs::canonicalize(&path).unwrap_or_else(|_| PathBuf::from(&path));
+  watchers.0.lock()?.remove(&root);
+  Ok(())
+}
+
+// Per-call options shared by every file in a read_ascii_files request
+struct AsciiReadOptions<'a> {
+  max_bytes: usize,
+  selections: &'a [LineSelection],
+  expand_tabs: Option<usize>,
+  normalize_newlines: bool,
+}
 
-  let mut delim_builder = regex::RegexBuilder::new(&config.delimiter);
-  if let Some(f) = &config.flags {
-    if f.contains('i') { delim_builder.case_insensitive(true); }
-    if f.contains('m') { delim_builder.multi_line(true); }
-    if f.contains('s') { delim_builder.dot_matches_new_line(true); }
+// None when `p` is not a regular file
+fn read_ascii_file(p: String, opts: &AsciiReadOptions) -> Result<Option<FileValue>, CommandError> {
+  let pb = PathBuf::from(&p);
+  if !pb.is_file() {
+    return Ok(None);
+  }
+  let f = File::open(&pb).map_err(|e| CommandError::io_at(&p, e))?;
+  let reader = BufReader::new(f);
+  let mut text = ascii_only_string(reader, opts.max_bytes).map_err(|e| CommandError::io_at(&p, e))?;
+  // CRLF and lone CR (old Mac) both become \n
+  if opts.normalize_newlines {
+    text = text.replace("\r\n", "\n").replace('\r', "\n");
   }
-  let delim = delim_builder.build().map_err(|e| e.to_string())?;
+  // Only the requested lines, when a selection targets this path
+  if let Some(sel) = opts.selections.iter().find(|s| s.path == p) {
+    text = select_line_range(&text, sel.start_line, sel.end_line);
+  }
+  // Each tab becomes N spaces so alignment doesn't depend on the viewer
+  if let Some(width) = opts.expand_tabs {
+    text = text.replace('\t', &" ".repeat(width));
+  }
+  Ok(Some(FileValue::new(p, text)))
+}
 
-  let id_re = if let Some(idc) = &config.id_capture {
-    let mut idb = regex::RegexBuilder::new(idc);
-    if let Some(f) = &config.flags {
-      if f.contains('i') { idb.case_insensitive(true); }
-      if f.contains('m') { idb.multi_line(true); }
-      if f.contains('s') { idb.dot_matches_new_line(true); }
+// Directories are replaced, in place, by their text files as scan_dir would list them
+fn expand_dir_paths(paths: Vec<String>) -> Result<Vec<String>, CommandError> {
+  let mut out = Vec::with_capacity(paths.len());
+  for p in paths {
+    let pb = PathBuf::from(&p);
+    if !pb.is_dir() {
+      out.push(p);
+      continue;
     }
-    Some(idb.build().map_err(|e| e.to_string())?)
-  } else { None };
+    let tree = build_tree_with_gitignore(&pb, &ScanOptions::default(), None).map_err(|e| CommandError::io_at(&p, e))?;
+    let mut files = Vec::new();
+    collect_file_paths(&tree, &mut files);
+    out.extend(files.into_iter().filter(|f| !is_probably_binary(Path::new(f))));
+  }
+  Ok(out)
+}
 
-  // Slice text by delimiter occurrences
-  let mut units: Vec<PromptUnit> = Vec::new();
-  let mut starts: Vec<usize> = delim.find_iter(&text).map(|m| m.start()).collect();
-  if starts.is_empty() {
-    // No delimiter found → create one whole unit
-    let id = id_re.as_ref()
-      .and_then(|re| re.captures(&text).and_then(|c| c.get(1)).map(|m| m.as_str().to_string()))
-      .unwrap_or_else(|| "1".into());
-    let body = text.trim().to_string();
-    if !body.is_empty() {
-      units.push(PromptUnit { id, body, meta: None });
+// `f` over every item on at most `workers` threads (each pulls the next index when
+// free), so no more than `workers` calls are ever in flight; results keep item order
+fn bounded_map<T: Sync, R: Send>(items: &[T], workers: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+  let workers = workers.clamp(1, items.len().max(1));
+  let next = std::sync::atomic::AtomicUsize::new(0);
+  let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+    let handles: Vec<_> = (0..workers).map(|_| scope.spawn(|| {
+      let mut done = Vec::new();
+      loop {
+        let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let Some(item) = items.get(i) else { break; };
+        done.push((i, f(item)));
+      }
+      done
+    })).collect();
+    handles.into_iter().flat_map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect()
+  });
+  results.sort_by_key(|(i, _)| *i);
+  results.into_iter().map(|(_, r)| r).collect()
+}
+
+// Files are read on up to `max_concurrency` threads (default: available cores);
+// results keep the order of `paths` and the first failing path's error wins
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(files = paths.len()), err)]
+fn read_ascii_files(
+  paths: Vec<String>,
+  max_bytes: Option<usize>,
+  selections: Option<Vec<LineSelection>>,
+  expand_tabs: Option<usize>,
+  normalize_newlines: Option<bool>,
+  max_concurrency: Option<usize>,
+  recurse_dirs: Option<bool>,
+) -> Result<Vec<FileValue>, CommandError> {
+  let paths = if recurse_dirs.unwrap_or(false) { expand_dir_paths(paths)? } else { paths };
+  let selections = selections.unwrap_or_default();
+  let opts = AsciiReadOptions {
+    max_bytes: max_bytes.unwrap_or(512 * 1024),
+    selections: &selections,
+    expand_tabs,
+    normalize_newlines: normalize_newlines.unwrap_or(false),
+  };
+  let workers = max_concurrency.unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |n| n.get()));
+  let results = bounded_map(&paths, workers, |p| read_ascii_file(p.clone(), &opts));
+
+  let mut out = Vec::with_capacity(results.len());
+  for r in results {
+    if let Some(v) = r? {
+      out.push(v);
     }
-    return Ok(units);
   }
-  starts.insert(0, 0);
-  starts.push(text.len());
+  tracing::info!(read = out.len(), "read files");
+  Ok(out)
+}
 
-  for w in starts.windows(2) {
-    let s = w[0];
-    let e = w[1];
-    if e <= s { continue; }
-    let block = text[s..e].trim();
-    if block.is_empty() { continue; }
-    let id = if let Some(re) = &id_re {
-      re.captures(block).and_then(|c| c.get(1)).map(|m| m.as_str().to_string())
-        .unwrap_or_else(|| format!("{}", units.len()+1))
+// One streaming pass: keeps the first `head_lines`, a ring of the last `tail_lines`,
+// and a count of everything in between, so memory stays bounded for huge logs
+#[tauri::command]
+fn read_head_tail(path: String, head_lines: usize, tail_lines: usize) -> Result<FileValue, CommandError> {
+  use std::io::BufRead;
+  let f = File::open(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let mut reader = BufReader::new(f);
+
+  // Grown as lines arrive: huge head/tail counts on a short file must not allocate up front
+  let mut head: Vec<String> = Vec::new();
+  let mut tail: std::collections::VecDeque<String> = std::collections::VecDeque::new();
+  let mut total = 0usize;
+  let mut buf = Vec::new();
+  loop {
+    buf.clear();
+    if reader.read_until(b'\n', &mut buf).map_err(|e| CommandError::io_at(&path, e))? == 0 { break; }
+    let line = String::from_utf8_lossy(&buf).trim_end_matches(['\n', '\r']).to_string();
+    total += 1;
+    if head.len() < head_lines {
+      head.push(line);
+    } else if tail_lines > 0 {
+      if tail.len() == tail_lines { tail.pop_front(); }
+      tail.push_back(line);
+    }
+  }
+
+  let omitted = total - head.len() - tail.len();
+  let mut lines = head;
+  if omitted > 0 {
+    lines.push(format!("... ({} lines omitted) ...", omitted));
+  }
+  lines.extend(tail);
+  Ok(FileValue::new(path, lines.join("\n")))
+}
+
+// SplitMix64: tiny, fast, and good enough for picking samples (not for secrets)
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+  // A fixed seed reproduces the same sample; otherwise seeded from the clock
+  fn new(seed: Option<u64>) -> Self {
+    let seed = seed.unwrap_or_else(|| {
+      SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+    });
+    SplitMix64(seed)
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+
+  // Uniform in 0..=max (modulo bias is negligible at these sizes)
+  fn up_to(&mut self, max: usize) -> usize {
+    (self.next_u64() % (max as u64 + 1)) as usize
+  }
+}
+
+// Reservoir sampling (Algorithm R): `n` items from a stream of unknown length in
+// one pass, returned with their stream index and in stream order
+fn reservoir_sample<T>(items: impl Iterator<Item = T>, n: usize, rng: &mut SplitMix64) -> Vec<(usize, T)> {
+  // `n` comes from the caller and may far exceed the stream, so grow as items arrive
+  let mut reservoir: Vec<(usize, T)> = Vec::new();
+  if n == 0 { return reservoir; }
+  for (i, item) in items.enumerate() {
+    if reservoir.len() < n {
+      reservoir.push((i, item));
+    } else {
+      let j = rng.up_to(i);
+      if j < n { reservoir[j] = (i, item); }
+    }
+  }
+  reservoir.sort_by_key(|(i, _)| *i);
+  reservoir
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SampledLine {
+  line_number: usize,
+  line: String,
+}
+
+// Streams the file, so only the sample is ever held in memory
+#[tauri::command]
+fn sample_lines(path: String, n: usize, seed: Option<u64>) -> Result<Vec<SampledLine>, CommandError> {
+  use std::io::BufRead;
+  let f = File::open(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let mut lines = Vec::new();
+  let mut io_err = None;
+  let iter = BufReader::new(f).split(b'\n').map_while(|r| r.map_err(|e| io_err = Some(e)).ok());
+  for (i, raw) in reservoir_sample(iter, n, &mut SplitMix64::new(seed)) {
+    let line = String::from_utf8_lossy(&raw).trim_end_matches('\r').to_string();
+    lines.push(SampledLine { line_number: i + 1, line });
+  }
+  if let Some(e) = io_err {
+    return Err(CommandError::io_at(&path, e));
+  }
+  Ok(lines)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConcatFiles {
+  text: String,
+  truncated: bool,
+  files_included: usize,
+}
+
+const DEFAULT_FILE_HEADER: &str = "=== {path} ===";
+
+fn render_path_template(template: &str, path: &str) -> String {
+  template.replace("{path}", path)
+}
+
+#[tauri::command]
+fn concat_files(paths: Vec<String>, max_bytes: Option<usize>, header_template: Option<String>) -> Result<ConcatFiles, CommandError> {
+  let template = header_template.unwrap_or_else(|| DEFAULT_FILE_HEADER.to_string());
+  concat_paths(paths, max_bytes.unwrap_or(512 * 1024), &template)
+}
+
+fn concat_paths(paths: Vec<String>, max: usize, template: &str) -> Result<ConcatFiles, CommandError> {
+  let mut text = String::new();
+  let mut truncated = false;
+  let mut files_included = 0;
+
+  for p in paths {
+    let pb = PathBuf::from(&p);
+    if !pb.is_file() { continue; }
+
+    let sep = if text.is_empty() { "" } else { "\n\n" };
+    let header = render_path_template(template, &p);
+    let used = text.len() + sep.len() + header.len() + 1;
+    if used >= max {
+      truncated = true;
+      break;
+    }
+    let budget = max - used;
+
+    let f = File::open(&pb).map_err(|e| CommandError::io_at(&p, e))?;
+    let file_len = f.metadata().map(|m| m.len()).unwrap_or(0);
+    let body = ascii_only_string(BufReader::new(f), budget).map_err(|e| CommandError::io_at(&p, e))?;
+
+    text.push_str(sep);
+    text.push_str(&header);
+    text.push('\n');
+    text.push_str(&body);
+    files_included += 1;
+
+    if file_len > budget as u64 {
+      truncated = true;
+      break;
+    }
+  }
+
+  Ok(ConcatFiles { text, truncated, files_included })
+}
+
+// A NUL byte in the first 8 KB marks a file as binary
+fn is_probably_binary(path: &Path) -> bool {
+  let mut head = Vec::with_capacity(8192);
+  match File::open(path) {
+    Ok(f) => f.take(8192).read_to_end(&mut head).is_err() || head.contains(&0),
+    Err(_) => true,
+  }
+}
+
+// Every text file under `path` (ignore rules applied) as one unit named after the directory
+#[tauri::command]
+fn extract_dir_as_unit(path: String, max_bytes: Option<usize>, header_template: Option<String>) -> Result<PromptUnit, CommandError> {
+  let root = PathBuf::from(&path);
+  if !root.is_dir() {
+    return Err(CommandError::NotFound);
+  }
+  let tree = build_tree_with_gitignore(&root, &ScanOptions::default(), None).map_err(|e| CommandError::io_at(&path, e))?;
+  let mut files = Vec::new();
+  collect_file_paths(&tree, &mut files);
+  files.retain(|f| !is_probably_binary(Path::new(f)));
+
+  let template = header_template.unwrap_or_else(|| DEFAULT_FILE_HEADER.to_string());
+  let total_files = files.len();
+  let concat = concat_paths(files, max_bytes.unwrap_or(512 * 1024), &template)?;
+  Ok(PromptUnit {
+    id: tree.name,
+    body: concat.text,
+    meta: Some(serde_json::json!({
+      "filesIncluded": concat.files_included,
+      "totalFiles": total_files,
+      "truncated": concat.truncated,
+    })),
+  })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileBase64 {
+  file_path: String,
+  base64: String,
+  mime: String,
+}
+
+#[tauri::command]
+fn read_file_base64(path: String, max_bytes: Option<usize>) -> Result<FileBase64, CommandError> {
+  let max = max_bytes.unwrap_or(10 * 1024 * 1024);
+  let pb = PathBuf::from(&path);
+  let len = fs::metadata(&pb).map_err(|e| CommandError::io_at(&path, e))?.len();
+  if len > max as u64 {
+    return Err(CommandError::Config(format!("{} is {} bytes, over the {} byte limit", path, len, max)));
+  }
+  let bytes = fs::read(&pb).map_err(|e| CommandError::io_at(&path, e))?;
+  let mime = mime_for_path(&pb).to_string();
+  Ok(FileBase64 { file_path: path, base64: BASE64_STANDARD.encode(bytes), mime })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchHit {
+  path: String,
+  line_number: usize,
+  line: String,
+  /// Lines around the match (the match included), starting at `context_start`;
+  /// before-context already shown with the previous hit in the same file is not
+  /// repeated, but the match line itself is always included
+  context: Vec<String>,
+  context_start: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GroupedSearch {
+  hits: Vec<SearchHit>,
+  /// Hit counts per directory, rolled up into every ancestor up to the searched root
+  by_directory: HashMap<String, usize>,
+}
+
+// A plain hit array, or `{ hits, byDirectory }` when grouping was requested
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SearchResult {
+  Hits(Vec<SearchHit>),
+  Grouped(GroupedSearch),
+}
+
+fn count_hits_by_directory(root: &Path, hits: &[SearchHit]) -> HashMap<String, usize> {
+  let mut counts = HashMap::new();
+  for h in hits {
+    let mut dir = Path::new(&h.path).parent();
+    while let Some(d) = dir {
+      *counts.entry(d.to_string_lossy().to_string()).or_insert(0) += 1;
+      if d == root { break; }
+      dir = d.parent();
+    }
+  }
+  counts
+}
+
+// File paths of a scanned tree, in display order
+fn collect_file_paths(node: &FileNode, out: &mut Vec<String>) {
+  for c in node.children.iter().flatten() {
+    if c.is_dir {
+      collect_file_paths(c, out);
     } else {
-      format!("{}", units.len()+1)
+      out.push(c.path.clone());
+    }
+  }
+}
+
+// Binary files (a NUL in the first 8 KB) are skipped by search
+fn read_searchable_text(path: &Path) -> Option<String> {
+  let data = fs::read(path).ok()?;
+  if data.iter().take(8192).any(|&b| b == 0) {
+    return None;
+  }
+  Some(decode_text(&data))
+}
+
+// `whole_word` only wraps plain literals; a real regex is left as written
+fn build_search_regex(pattern: &str, case_insensitive: bool, whole_word: bool) -> Result<regex::Regex, regex::Error> {
+  let is_literal = regex::escape(pattern) == pattern;
+  let source = if whole_word && is_literal { format!(r"\b{}\b", pattern) } else { pattern.to_string() };
+  build_regex(&source, if case_insensitive { Some("i") } else { None })
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+#[allow(clippy::too_many_arguments)]
+fn search_files(
+  path: String,
+  pattern: String,
+  case_insensitive: Option<bool>,
+  whole_word: Option<bool>,
+  max_results: Option<usize>,
+  context_before: Option<usize>,
+  context_after: Option<usize>,
+  group_by_directory: Option<bool>,
+) -> Result<SearchResult, CommandError> {
+  let root = PathBuf::from(&path);
+  if !root.is_dir() {
+    return Err(CommandError::NotFound);
+  }
+  if pattern.is_empty() {
+    return Err(CommandError::Config("Search pattern is empty".into()));
+  }
+  let re = build_search_regex(&pattern, case_insensitive.unwrap_or(false), whole_word.unwrap_or(false))?;
+  let max = max_results.unwrap_or(1000);
+  let before = context_before.unwrap_or(0);
+  let after = context_after.unwrap_or(0);
+
+  let opts = ScanOptions::default();
+  let tree = build_tree_with_gitignore(&root, &opts, None).map_err(|e| CommandError::io_at(&path, e))?;
+  let mut files = Vec::new();
+  collect_file_paths(&tree, &mut files);
+
+  let mut hits = Vec::new();
+  'files: for f in files {
+    let Some(text) = read_searchable_text(Path::new(&f)) else { continue; };
+    let lines: Vec<&str> = text.lines().collect();
+    // First line index not yet covered by an earlier hit's context
+    let mut shown_until = 0;
+    for (i, line) in lines.iter().enumerate() {
+      if !re.is_match(line) { continue; }
+      if hits.len() >= max { break 'files; }
+      let start = i.saturating_sub(before).max(shown_until).min(i);
+      let end = (i + after + 1).min(lines.len());
+      shown_until = end;
+      hits.push(SearchHit {
+        path: f.clone(),
+        line_number: i + 1,
+        line: line.trim_end().to_string(),
+        context: lines[start..end].iter().map(|l| l.trim_end().to_string()).collect(),
+        context_start: start + 1,
+      });
+    }
+  }
+  if group_by_directory.unwrap_or(false) {
+    let by_directory = count_hits_by_directory(&root, &hits);
+    return Ok(SearchResult::Grouped(GroupedSearch { hits, by_directory }));
+  }
+  Ok(SearchResult::Hits(hits))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DuplicateGroup {
+  hash: String,
+  size: u64,
+  paths: Vec<String>,
+}
+
+// Groups by size first so only same-sized files are ever hashed; empty files are skipped
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn find_duplicate_files(path: String) -> Result<Vec<DuplicateGroup>, CommandError> {
+  let root = PathBuf::from(&path);
+  if !root.is_dir() {
+    return Err(CommandError::NotFound);
+  }
+  let opts = ScanOptions::default();
+  let tree = build_tree_with_gitignore(&root, &opts, None).map_err(|e| CommandError::io_at(&path, e))?;
+  let mut files = Vec::new();
+  collect_file_paths(&tree, &mut files);
+
+  let mut by_size: HashMap<u64, Vec<String>> = HashMap::new();
+  for f in files {
+    let size = fs::metadata(&f).map_err(|e| CommandError::io_at(&f, e))?.len();
+    if size > 0 {
+      by_size.entry(size).or_default().push(f);
+    }
+  }
+
+  let mut groups = Vec::new();
+  for (size, paths) in by_size.into_iter().filter(|(_, v)| v.len() > 1) {
+    let mut by_hash: HashMap<String, Vec<String>> = HashMap::new();
+    for f in paths {
+      let data = fs::read(&f).map_err(|e| CommandError::io_at(&f, e))?;
+      by_hash.entry(sha256_hex(&data)).or_default().push(f);
+    }
+    groups.extend(
+      by_hash.into_iter()
+        .filter(|(_, v)| v.len() > 1)
+        .map(|(hash, paths)| DuplicateGroup { hash, size, paths }),
+    );
+  }
+  groups.sort_by(|a, b| a.paths[0].cmp(&b.paths[0]));
+  Ok(groups)
+}
+
+// One digest for a selection: order-independent, and changed by any edit, addition,
+// removal or deletion. Size + mtime by default (cheap); `by_contents` hashes the
+// bytes instead, so touching a file without changing it keeps the fingerprint.
+#[tauri::command]
+fn fingerprint_paths(paths: Vec<String>, by_contents: Option<bool>) -> Result<String, CommandError> {
+  let mut paths = paths;
+  paths.sort();
+  paths.dedup();
+
+  let mut hasher = Sha256::new();
+  for p in &paths {
+    hasher.update(p.as_bytes());
+    hasher.update([0u8]);
+    let md = match fs::metadata(p) {
+      Ok(md) => md,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        hasher.update(b"missing\n");
+        continue;
+      }
+      Err(e) => return Err(CommandError::io_at(p, e)),
     };
-    units.push(PromptUnit { id, body: block.to_string(), meta: None });
+    if by_contents.unwrap_or(false) && md.is_file() {
+      let data = fs::read(p).map_err(|e| CommandError::io_at(p, e))?;
+      hasher.update(Sha256::digest(&data));
+    } else {
+      let mtime = md.modified().ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+      hasher.update(format!("{}:{}", md.len(), mtime).as_bytes());
+    }
+    hasher.update(b"\n");
+  }
+  Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[tauri::command]
+fn load_system_prompt(app: AppHandle) -> Result<String, CommandError> {
+  let config_dir = app
+    .path()
+    .app_config_dir()
+    .map_err(|e| CommandError::Io(e.to_string()))?;
+  let file_path = config_dir.join(SYSTEM_PROMPT_FILENAME);
+
+  if !file_path.exists() {
+    return Ok(String::new());
+  }
+
+  let contents = fs::read_to_string(&file_path)?;
+  Ok(contents)
+}
+
+#[tauri::command]
+fn save_system_prompt(app: AppHandle, value: String) -> Result<(), CommandError> {
+  let config_dir = app
+    .path()
+    .app_config_dir()
+    .map_err(|e| CommandError::Io(e.to_string()))?;
+
+  if !config_dir.exists() {
+    create_dir_all(&config_dir)?;
+  }
+
+  let file_path = config_dir.join(SYSTEM_PROMPT_FILENAME);
+  fs::write(&file_path, value)?;
+  Ok(())
+}
+
+/* ====================== Entry point wired for main.rs ====================== */
+
+pub fn run() {
+  // RUST_LOG overrides the default (info; debug adds request URLs and statuses)
+  let _ = tracing_subscriber::fmt()
+    .with_env_filter(
+      tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+    )
+    .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+    .try_init();
+
+  tauri::Builder::default()
+    // plugins you use on the frontend
+    .plugin(tauri_plugin_dialog::init())
+    .plugin(tauri_plugin_clipboard_manager::init())
+    .plugin(tauri_plugin_fs::init())
+    .manage(ScanCache::default())
+    .manage(DirWatchers::default())
+    // register commands
+    .invoke_handler(tauri::generate_handler![
+      scan_dir,
+      clear_scan_cache,
+      watch_dir,
+      unwatch_dir,
+      list_files,
+      scan_summary,
+      diff_scans,
+      search_files,
+      find_duplicate_files,
+      fingerprint_paths,
+      read_ascii_files,
+      read_head_tail,
+      sample_lines,
+      sample_excel_rows,
+      read_file_base64,
+      concat_files,
+      extract_dir_as_unit,
+      inspect_excel,
+      list_named_ranges,
+      detect_excel_header,
+      extract_excel_units,
+      extract_excel_units_batch,
+      preview_excel_units,
+      preview_excel,
+      extract_regex_blocks,
+      regex_blocks_as_table,
+      count_regex_blocks,
+      suggest_delimiters,
+      extract_paragraphs,
+      extract_markdown_docs,
+      extract_code_blocks,
+      extract_html_blocks,
+      extract_html_blocks_from_url,
+      download_url_to_file,
+      ping_endpoint,
+      validate_selector,
+      html_to_text,
+      extract_html_tables,
+      extract_json_units,
+      extract_auto,
+      extract_api_units,            // <— add this line
+      fetch_api_table,            // <-- add this
+      fetch_api_table_from_url,
+      fetch_api_table_from_urls,
+      merge_tables,
+      table_to_units,
+      merge_units,
+      dedupe_units,
+      estimate_cost,
+      flag_oversized_units,
+      export_markdown,
+      export_chat_jsonl,
+      save_units_with_manifest,
+      preview_filenames,
+      split_file_to_dir,
+      chunk_text,
+      load_system_prompt,       // NEW
+      save_system_prompt        // NEW
+    ])
+    .run(tauri::generate_context!())
+    .expect("error while running tauri application");
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptUnit {
+  id: String,
+  body: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  meta: Option<serde_json::Value>,
+}
+
+// Id for a unit whose own id is missing: the plain number by default, or a template
+// with {n} / zero-padded {n:04} ("unit-{n:04}" -> "unit-0001")
+fn fallback_id(template: Option<&str>, n: usize) -> String {
+  let Some(template) = template else { return n.to_string(); };
+  let mut out = String::new();
+  let mut rest = template;
+  while let Some(start) = rest.find("{n") {
+    out.push_str(&rest[..start]);
+    let after = &rest[start + 2..];
+    let rendered = after.find('}').and_then(|end| {
+      let spec = &after[..end];
+      let text = if spec.is_empty() {
+        n.to_string()
+      } else {
+        let width: usize = spec.strip_prefix(":0")?.parse().ok()?;
+        format!("{:0width$}", n, width = width)
+      };
+      Some((text, end + 1))
+    });
+    match rendered {
+      Some((text, used)) => {
+        out.push_str(&text);
+        rest = &after[used..];
+      }
+      None => {
+        out.push_str("{n");
+        rest = after;
+      }
+    }
+  }
+  out.push_str(rest);
+  out
+}
+
+// Adds one key to a unit's meta, creating the object when there is none yet
+fn with_meta_entry(meta: Option<Value>, key: &str, value: Value) -> Option<Value> {
+  let mut map = match meta {
+    Some(Value::Object(m)) => m,
+    _ => Map::new(),
+  };
+  map.insert(key.into(), value);
+  Some(Value::Object(map))
+}
+
+// `keep_empty`: empty-body units are emitted anyway, marked with meta.skippedReason
+fn with_skipped_reason(meta: Option<Value>, reason: &str) -> Option<Value> {
+  with_meta_entry(meta, "skippedReason", Value::String(reason.into()))
+}
+
+// File extractors record their input in meta.sourcePath so mixed sources stay traceable
+fn tag_source_path(units: &mut [PromptUnit], path: &str) {
+  for u in units.iter_mut() {
+    u.meta = with_meta_entry(u.meta.take(), "sourcePath", Value::String(path.to_string()));
+  }
+}
+
+// Extractor limits shared by every config: `min_body_len` counts chars
+fn below_min_len(body: &str, min_body_len: Option<usize>) -> bool {
+  min_body_len.is_some_and(|min| body.chars().count() < min)
+}
+
+fn reached_max_units(count: usize, max_units: Option<usize>) -> bool {
+  max_units.is_some_and(|max| count >= max)
+}
+
+// Strip trailing whitespace per line and collapse 3+ newlines into one blank line
+fn normalize_whitespace(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut blank_run = 0;
+  for line in text.lines() {
+    let line = line.trim_end();
+    if line.is_empty() {
+      blank_run += 1;
+      if blank_run > 1 { continue; }
+    } else {
+      blank_run = 0;
+    }
+    if !out.is_empty() { out.push('\n'); }
+    out.push_str(line);
+  }
+  out.trim_end().to_string()
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+  Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// "auto" keeps derived ids, "sequential" renumbers 1..n, "hash" uses the body digest
+fn apply_id_mode(units: &mut [PromptUnit], id_mode: Option<&str>) -> Result<(), CommandError> {
+  match id_mode.unwrap_or("auto") {
+    "auto" => {}
+    "sequential" => {
+      for (i, u) in units.iter_mut().enumerate() { u.id = (i + 1).to_string(); }
+    }
+    "hash" => {
+      for u in units.iter_mut() { u.id = sha256_hex(u.body.trim().as_bytes())[..12].to_string(); }
+    }
+    other => return Err(CommandError::Config(format!("Unknown idMode: {}", other))),
+  }
+  Ok(())
+}
+
+// "none" keeps ids as derived; "lower" / "upper" fold them for case-insensitive consumers
+fn apply_id_case(units: &mut [PromptUnit], id_case: Option<&str>) -> Result<(), CommandError> {
+  match id_case.unwrap_or("none") {
+    "none" => {}
+    "lower" => {
+      for u in units.iter_mut() { u.id = u.id.to_lowercase(); }
+    }
+    "upper" => {
+      for u in units.iter_mut() { u.id = u.id.to_uppercase(); }
+    }
+    other => return Err(CommandError::Config(format!("Unknown idCase: {}", other))),
+  }
+  Ok(())
+}
+
+/* ---------- Excel inspector ---------- */
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExcelSheetInfo {
+  name: String,
+  columns: Vec<String>,
+  // Header cells exactly as stored (untrimmed; "" where columns has a colN placeholder)
+  raw_columns: Vec<String>,
+  row_count: usize,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExcelInspector {
+  path: String,
+  sheets: Vec<ExcelSheetInfo>,
+  errors: Vec<ExcelSheetError>,   // sheets that could not be read
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExcelSheetError {
+  sheet: String,
+  message: String,
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn inspect_excel(path: String) -> Result<ExcelInspector, CommandError> {
+  let p = PathBuf::from(&path);
+  if !p.exists() { return Err(CommandError::NotFound); }
+  let mut wb = open_workbook_auto(&p)?;
+
+  let mut sheets: Vec<ExcelSheetInfo> = Vec::new();
+  let mut errors: Vec<ExcelSheetError> = Vec::new();
+
+  for sname in wb.sheet_names().to_owned() {
+    let range = match wb.worksheet_range(&sname) {
+      Some(Ok(range)) => range,
+      Some(Err(e)) => {
+        errors.push(ExcelSheetError { sheet: sname, message: e.to_string() });
+        continue;
+      }
+      None => {
+        errors.push(ExcelSheetError { sheet: sname, message: "Sheet is listed but its data could not be found".into() });
+        continue;
+      }
+    };
+    // Find header row (first non-empty row)
+    let mut header: Vec<String> = Vec::new();
+    let mut raw_header: Vec<String> = Vec::new();
+    'rows: for row in range.rows() {
+      if row.iter().any(|c| !c.is_empty()) {
+        raw_header = row.iter().map(|c| match c {
+          DataType::String(s) => s.clone(),
+          DataType::Float(f) => format!("{}", f),
+          DataType::Int(i) => format!("{}", i),
+          DataType::Bool(b) => format!("{}", b),
+          _ => String::new(),
+        }).collect();
+        header = row.iter().enumerate().map(|(i, c)| {
+          match c {
+            DataType::String(s) => if s.trim().is_empty() { format!("col{}", i+1) } else { s.trim().to_string() },
+            DataType::Float(f) => format!("{}", f),
+            DataType::Int(i) => format!("{}", i),
+            DataType::Bool(b) => format!("{}", b),
+            _ => format!("col{}", i+1),
+          }
+        }).collect();
+        break 'rows;
+      }
+    }
+    if header.is_empty() {
+      // Fallback: number columns based on first row length
+      if let Some(first) = range.rows().next() {
+        header = (0..first.len()).map(|i| format!("col{}", i+1)).collect();
+        raw_header = vec![String::new(); first.len()];
+      }
+    }
+    let row_count = if range.is_empty() { 0 } else { range.rows().count() };
+    sheets.push(ExcelSheetInfo { name: sname.to_string(), columns: header, raw_columns: raw_header, row_count });
+  }
+
+  Ok(ExcelInspector { path, sheets, errors })
+}
+
+/* ---------- Excel named ranges ---------- */
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NamedRange {
+  name: String,
+  sheet: Option<String>,   // None for names that are constants or formulas
+  range: String,
+}
+
+// "Sheet1!$A$1:$C$10" or "'My Sheet'!B2" -> (sheet, reference)
+fn split_sheet_ref(formula: &str) -> Option<(String, &str)> {
+  let (sheet, reference) = formula.trim_start_matches('=').rsplit_once('!')?;
+  let sheet = match sheet.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+    Some(quoted) => quoted.replace("''", "'"),
+    None => sheet.to_string(),
+  };
+  Some((sheet, reference))
+}
+
+// "$B$12" -> zero-based (row, col); None for anything that does not fit a u32
+fn parse_cell_ref(cell: &str) -> Option<(u32, u32)> {
+  let cell = cell.replace('$', "");
+  let split = cell.find(|c: char| c.is_ascii_digit())?;
+  let (letters, digits) = cell.split_at(split);
+  if letters.is_empty() { return None; }
+  let mut col: u32 = 0;
+  for c in letters.chars() {
+    if !c.is_ascii_alphabetic() { return None; }
+    col = col.checked_mul(26)?.checked_add(c.to_ascii_uppercase() as u32 - 'A' as u32 + 1)?;
+  }
+  let row: u32 = digits.parse().ok()?;
+  if row == 0 { return None; }
+  Some((row - 1, col - 1))
+}
+
+// "A1:C10" (or a single cell) -> inclusive zero-based corners
+fn parse_area_ref(reference: &str) -> Option<((u32, u32), (u32, u32))> {
+  let (a, b) = reference.split_once(':').unwrap_or((reference, reference));
+  Some((parse_cell_ref(a)?, parse_cell_ref(b)?))
+}
+
+// A sheet by name, or else a workbook-defined name pointing at one rectangular area
+fn load_sheet_range(wb: &mut Workbook, sheet: &str) -> Result<(String, calamine::Range<DataType>), CommandError> {
+  if wb.sheet_names().iter().any(|s| s == sheet) {
+    let range = wb.worksheet_range(sheet)
+      .ok_or_else(|| CommandError::Config(format!("Sheet not found: {}", sheet)))??;
+    return Ok((sheet.to_string(), range));
+  }
+  let formula = wb.defined_names().iter()
+    .find(|(name, _)| name == sheet)
+    .map(|(_, formula)| formula.clone())
+    .ok_or_else(|| CommandError::Config(format!("Sheet or named range not found: {}", sheet)))?;
+  let (sheet_name, (start, end)) = split_sheet_ref(&formula)
+    .and_then(|(s, r)| parse_area_ref(r).map(|area| (s, area)))
+    .ok_or_else(|| CommandError::Config(format!("Named range {} is not a single cell area: {}", sheet, formula)))?;
+  let full = wb.worksheet_range(&sheet_name)
+    .ok_or_else(|| CommandError::Config(format!("Sheet not found: {}", sheet_name)))??;
+  // Corners may come in either order. The area is cut down to the sheet's used cells,
+  // so a whole-sheet name like A1:XFD1048576 does not allocate every cell
+  let (Some(used_start), Some(used_end)) = (full.start(), full.end()) else {
+    return Ok((sheet_name, calamine::Range::empty()));
+  };
+  let top_left = (start.0.min(end.0).max(used_start.0), start.1.min(end.1).max(used_start.1));
+  let bottom_right = (start.0.max(end.0).min(used_end.0), start.1.max(end.1).min(used_end.1));
+  if top_left.0 > bottom_right.0 || top_left.1 > bottom_right.1 {
+    return Ok((sheet_name, calamine::Range::empty()));
+  }
+  Ok((sheet_name, full.range(top_left, bottom_right)))
+}
+
+#[tauri::command]
+fn list_named_ranges(path: String) -> Result<Vec<NamedRange>, CommandError> {
+  let p = PathBuf::from(&path);
+  if !p.exists() { return Err(CommandError::NotFound); }
+  let wb = open_workbook_auto(&p)?;
+  Ok(wb.defined_names().iter().map(|(name, formula)| match split_sheet_ref(formula) {
+    Some((sheet, reference)) => NamedRange { name: name.clone(), sheet: Some(sheet), range: reference.to_string() },
+    None => NamedRange { name: name.clone(), sheet: None, range: formula.clone() },
+  }).collect())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExcelHeader {
+  header_row: usize,   // 0-based row in the sheet, counting leading blank rows
+  columns: Vec<String>,
+}
+
+#[tauri::command]
+fn detect_excel_header(path: String, sheet: String, header_scan_limit: Option<usize>) -> Result<ExcelHeader, CommandError> {
+  let mut wb = open_workbook_auto(&path)?;
+  let (_, range) = load_sheet_range(&mut wb, &sheet)?;
+  let (idx, columns) = detect_header_row(&range, header_scan_limit)?;
+  let origin = range.start().map_or(0, |(row, _)| row as usize);
+  Ok(ExcelHeader { header_row: origin + idx, columns })
+}
+
+/* ---------- Excel units ---------- */
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExcelConfig {
+  sheet: String,                 // sheet name, or a workbook named range
+  id_column: String,
+  description_columns: Vec<String>,
+  filters: Option<Vec<ExcelFilter>>,
+  max_units: Option<usize>,
+  min_body_len: Option<usize>,
+  use_formula_text: Option<bool>,
+  normalize_whitespace: Option<bool>,
+  id_prefix: Option<String>,
+  // "{sheet}:{id}" keeps ids unique when several sheets are extracted together
+  prefix_sheet_name: Option<bool>,
+  keep_empty: Option<bool>,
+  id_fallback_template: Option<String>,
+  // Report bad rows and missing description columns in `errors` instead of failing
+  tolerant: Option<bool>,
+  // Match columns ignoring case, spaces and punctuation; units then report the
+  // header names that were matched in meta.idColumn / meta.descriptionColumns
+  fuzzy_columns: Option<bool>,
+  // Rows examined for the header before giving up (default DEFAULT_HEADER_SCAN_LIMIT)
+  header_scan_limit: Option<usize>,
+  id_case: Option<String>,       // "none" (default) | "lower" | "upper"
+  // Prefix each body part with its header name ("Description: ...")
+  labeled: Option<bool>,
+}
+
+const DEFAULT_HEADER_SCAN_LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExcelFilter {
+  column: String,
+  equals: String,
+}
+
+// First non-empty row within the first `scan_limit` rows (default
+// DEFAULT_HEADER_SCAN_LIMIT) is the header; blank header cells become col{N}
+fn detect_header_row(range: &calamine::Range<DataType>, scan_limit: Option<usize>) -> Result<(usize, Vec<String>), CommandError> {
+  let scan_limit = scan_limit.unwrap_or(DEFAULT_HEADER_SCAN_LIMIT);
+  for (i, row) in range.rows().enumerate().take(scan_limit) {
+    if row.iter().any(|c| !c.is_empty()) {
+      let header = row.iter().enumerate().map(|(j, c)| cell_to_string(c).unwrap_or_else(|| format!("col{}", j+1))).collect();
+      return Ok((i, header));
+    }
+  }
+  Err(CommandError::Parse(format!(
+    "No header row found in the first {} rows; raise headerScanLimit if the header is further down",
+    scan_limit
+  )))
+}
+
+// "#N" is a zero-based column index (handy with duplicate headers), resolved like a
+// name only when it falls inside the header row; anything else is a case-insensitive header name
+fn resolve_column(header: &[String], spec: &str, fuzzy: bool) -> Option<usize> {
+  if let Some(idx) = spec.trim().strip_prefix('#').and_then(|n| n.trim().parse::<usize>().ok()) {
+    return (idx < header.len()).then_some(idx);
+  }
+  header.iter().position(|h| h.eq_ignore_ascii_case(spec)).or_else(|| {
+    if !fuzzy { return None; }
+    let key = fuzzy_column_key(spec);
+    header.iter().position(|h| !key.is_empty() && fuzzy_column_key(h) == key)
+  })
+}
+
+// "Product ID ", "product_id" and "P.I.D." style variants compare equal after this
+fn fuzzy_column_key(name: &str) -> String {
+  name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+// ExcelConfig column names resolved to indices against the detected header
+struct ExcelColumns {
+  header: Vec<String>,
+  header_idx: usize,
+  id_idx: usize,
+  desc_indices: Vec<usize>,
+  filters: Vec<(usize, String)>,
+  missing_columns: Vec<String>,
+}
+
+fn resolve_excel_columns(range: &calamine::Range<DataType>, config: &ExcelConfig) -> Result<ExcelColumns, CommandError> {
+  let (header_idx, header) = detect_header_row(range, config.header_scan_limit)?;
+
+  let fuzzy = config.fuzzy_columns.unwrap_or(false);
+  let id_idx = resolve_column(&header, &config.id_column, fuzzy)
+    .ok_or_else(|| CommandError::Config(format!("ID column not found: {}", config.id_column)))?;
+
+  // Tolerant mode drops missing description columns instead of failing
+  let mut desc_indices: Vec<usize> = Vec::new();
+  let mut missing_columns: Vec<String> = Vec::new();
+  for name in &config.description_columns {
+    match resolve_column(&header, name, fuzzy) {
+      Some(idx) => desc_indices.push(idx),
+      None if config.tolerant.unwrap_or(false) => missing_columns.push(name.clone()),
+      None => return Err(CommandError::Config(format!("Description column not found: {}", name))),
+    }
+  }
+
+  let filters: Vec<(usize, String)> = config.filters.iter().flatten()
+    .map(|f| resolve_column(&header, &f.column, fuzzy)
+      .map(|idx| (idx, f.equals.trim().to_string()))
+      .ok_or_else(|| CommandError::Config(format!("Filter column not found: {}", f.column))))
+    .collect::<Result<_,_>>()?;
+
+  Ok(ExcelColumns { header, header_idx, id_idx, desc_indices, filters, missing_columns })
+}
+
+// Every filter must match (trimmed equality) for a row to be kept
+fn row_passes_filters(row: &[DataType], filters: &[(usize, String)]) -> bool {
+  filters.iter().all(|(fi, want)| {
+    row.get(*fi).and_then(cell_to_string).unwrap_or_default().trim() == want
+  })
+}
+
+type Workbook = calamine::Sheets<BufReader<File>>;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExcelRowError {
+  row_index: Option<usize>,   // None for sheet-level problems such as a missing column
+  message: String,
+}
+
+#[derive(Serialize)]
+struct ExcelExtraction {
+  units: Vec<PromptUnit>,
+  errors: Vec<ExcelRowError>,
+}
+
+// A plain unit array, or `{ units, errors }` when the config asked for tolerant mode
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ExcelUnitsResult {
+  Units(Vec<PromptUnit>),
+  Tolerant(ExcelExtraction),
+}
+
+impl ExcelUnitsResult {
+  fn new(extraction: ExcelExtraction, config: &ExcelConfig) -> Self {
+    if config.tolerant.unwrap_or(false) {
+      ExcelUnitsResult::Tolerant(extraction)
+    } else {
+      ExcelUnitsResult::Units(extraction.units)
+    }
+  }
+
+  fn into_units(self) -> Vec<PromptUnit> {
+    match self {
+      ExcelUnitsResult::Units(units) => units,
+      ExcelUnitsResult::Tolerant(extraction) => extraction.units,
+    }
+  }
+
+  fn units_mut(&mut self) -> &mut Vec<PromptUnit> {
+    match self {
+      ExcelUnitsResult::Units(units) => units,
+      ExcelUnitsResult::Tolerant(extraction) => &mut extraction.units,
+    }
+  }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CappedExcelBatch {
+  results: Vec<ExcelUnitsResult>,
+  // Units dropped from each config's result to stay under the global cap
+  truncated: Vec<usize>,
+}
+
+// One result per config, or `{ results, truncated }` when a global cap was given
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ExcelBatchResult {
+  Results(Vec<ExcelUnitsResult>),
+  Capped(CappedExcelBatch),
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path, sheet = %config.sheet), err)]
+fn extract_excel_units(path: String, config: ExcelConfig) -> Result<ExcelUnitsResult, CommandError> {
+  let mut wb = open_workbook_auto(&path)?;
+  let extraction = extract_units_from_workbook(&mut wb, &config)?;
+  Ok(ExcelUnitsResult::new(extraction, &config))
+}
+
+// Open the workbook once and run every config against it. With `global_max_units`,
+// configs fill the budget in order and later ones are cut (or left empty) once it runs out.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path, configs = configs.len()), err)]
+fn extract_excel_units_batch(
+  path: String,
+  configs: Vec<ExcelConfig>,
+  global_max_units: Option<usize>,
+) -> Result<ExcelBatchResult, CommandError> {
+  let mut wb = open_workbook_auto(&path)?;
+  let mut results = configs.iter()
+    .map(|config| extract_units_from_workbook(&mut wb, config).map(|x| ExcelUnitsResult::new(x, config)))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let Some(cap) = global_max_units else { return Ok(ExcelBatchResult::Results(results)); };
+  let mut remaining = cap;
+  let truncated = results.iter_mut().map(|r| {
+    let units = r.units_mut();
+    let keep = units.len().min(remaining);
+    remaining -= keep;
+    let dropped = units.len() - keep;
+    units.truncate(keep);
+    dropped
+  }).collect();
+  Ok(ExcelBatchResult::Capped(CappedExcelBatch { results, truncated }))
+}
+
+fn extract_units_from_workbook(wb: &mut Workbook, config: &ExcelConfig) -> Result<ExcelExtraction, CommandError> {
+  let (sheet_name, range) = load_sheet_range(wb, &config.sheet)?;
+
+  let cols = resolve_excel_columns(&range, config)?;
+  let mut errors: Vec<ExcelRowError> = cols.missing_columns.iter()
+    .map(|name| ExcelRowError { row_index: None, message: format!("Description column not found: {}", name) })
+    .collect();
+  let cells = CellReader::new(wb, &sheet_name, &range, config);
+
+  let mut units: Vec<PromptUnit> = Vec::new();
+
+  for (i, row) in range.rows().enumerate() {
+    if i <= cols.header_idx { continue; }
+    if reached_max_units(units.len(), config.max_units) { break; }
+    match excel_row_outcome(i, row, &cols, config, &cells, units.len() + 1) {
+      RowOutcome::Unit(unit) => units.push(unit),
+      RowOutcome::ErrorValue(e) => errors.push(e),
+      _ => {}
+    }
+  }
+
+  apply_id_case(&mut units, config.id_case.as_deref())?;
+  tracing::info!(sheet = %sheet_name, rows = range.height(), units = units.len(), errors = errors.len(), "extracted Excel units");
+  Ok(ExcelExtraction { units, errors })
+}
+
+// Cell text as extraction sees it: with use_formula_text, a cell's formula (with a
+// leading '='); workbooks without formulas, and cells without one, give the value
+struct CellReader {
+  formulas: Option<calamine::Range<String>>,
+  origin: (u32, u32),
+}
+
+impl CellReader {
+  fn new(wb: &mut Workbook, sheet_name: &str, range: &calamine::Range<DataType>, config: &ExcelConfig) -> Self {
+    let formulas = if config.use_formula_text.unwrap_or(false) {
+      wb.worksheet_formula(sheet_name).and_then(|r| r.ok())
+    } else {
+      None
+    };
+    CellReader { formulas, origin: range.start().unwrap_or((0, 0)) }
+  }
+
+  fn text(&self, i: usize, j: usize, row: &[DataType]) -> Option<String> {
+    if let Some(f) = &self.formulas {
+      let pos = (self.origin.0 + i as u32, self.origin.1 + j as u32);
+      if let Some(t) = f.get_value(pos).filter(|t| !t.is_empty()) {
+        return Some(if t.starts_with('=') { t.clone() } else { format!("={}", t) });
+      }
+    }
+    row.get(j).and_then(cell_to_string)
+  }
+}
+
+// What happens to one data row; extract_excel_units and preview_excel_units both
+// go through excel_row_outcome so their counts cannot drift apart
+enum RowOutcome {
+  Filtered,
+  ErrorValue(ExcelRowError),   // tolerant mode only
+  EmptyId,
+  EmptyBody,
+  TooShort,
+  Unit(PromptUnit),
+}
+
+// `unit_number` is the 1-based position the unit would get, for fallback ids
+fn excel_row_outcome(
+  i: usize,
+  row: &[DataType],
+  cols: &ExcelColumns,
+  config: &ExcelConfig,
+  cells: &CellReader,
+  unit_number: usize,
+) -> RowOutcome {
+  if !row_passes_filters(row, &cols.filters) { return RowOutcome::Filtered; }
+  // In tolerant mode a row with an error value (#DIV/0!, #REF!, ...) in a used cell is reported, not extracted
+  if config.tolerant.unwrap_or(false) {
+    let bad = std::iter::once(cols.id_idx).chain(cols.desc_indices.iter().copied())
+      .find_map(|j| match row.get(j) {
+        Some(DataType::Error(e)) => Some((j, e.to_string())),
+        _ => None,
+      });
+    if let Some((j, e)) = bad {
+      let column = cols.header.get(j).cloned().unwrap_or_else(|| format!("#{}", j));
+      return RowOutcome::ErrorValue(ExcelRowError { row_index: Some(i), message: format!("{} has error value {}", column, e) });
+    }
+  }
+  let mut id = cells.text(i, cols.id_idx, row).unwrap_or_default().trim().to_string();
+  if id.is_empty() {
+    // Rows without an id are skipped unless a fallback template is configured
+    match config.id_fallback_template.as_deref() {
+      Some(t) => id = fallback_id(Some(t), unit_number),
+      None => return RowOutcome::EmptyId,
+    }
+  }
+
+  let mut parts: Vec<String> = Vec::new();
+  for &di in cols.desc_indices.iter() {
+    if let Some(s) = cells.text(i, di, row) {
+      let v = s.trim();
+      if v.is_empty() { continue; }
+      match cols.header.get(di) {
+        Some(label) if config.labeled.unwrap_or(false) => parts.push(format!("{}: {}", label, v)),
+        _ => parts.push(v.to_string()),
+      }
+    }
+  }
+  let mut body = parts.join("\n");
+  if config.normalize_whitespace.unwrap_or(false) { body = normalize_whitespace(&body); }
+  let placeholder = body.is_empty() && config.keep_empty.unwrap_or(false);
+  if !placeholder {
+    if body.is_empty() { return RowOutcome::EmptyBody; }
+    if below_min_len(&body, config.min_body_len) { return RowOutcome::TooShort; }
+  }
+
+  let id = if config.prefix_sheet_name.unwrap_or(false) { format!("{}:{}", config.sheet, id) } else { id };
+  let id = format!("{}{}", config.id_prefix.as_deref().unwrap_or(""), id);
+
+  let mut meta = serde_json::json!({
+    "sheet": config.sheet,
+    "rowIndex": i
+  });
+  if config.fuzzy_columns.unwrap_or(false) {
+    meta["idColumn"] = Value::from(cols.header.get(cols.id_idx).cloned());
+    meta["descriptionColumns"] = cols.desc_indices.iter().map(|&di| Value::from(cols.header.get(di).cloned())).collect();
+  }
+  let meta = Some(meta);
+  let meta = if placeholder { with_skipped_reason(meta, "empty_body") } else { meta };
+  RowOutcome::Unit(PromptUnit { id, body, meta })
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ExcelUnitsPreview {
+  total_rows: usize,
+  would_extract: usize,         // includes keepEmpty placeholders
+  skipped_filtered: usize,
+  skipped_error: usize,         // tolerant mode: rows with an error value
+  skipped_empty_id: usize,
+  skipped_empty_body: usize,
+  skipped_too_short: usize,     // under minBodyLen
+  skipped_over_max: usize,      // rows left once maxUnits was reached
+}
+
+// Same row rules as extract_excel_units (see excel_row_outcome), but only counts outcomes
+#[tauri::command]
+fn preview_excel_units(path: String, config: ExcelConfig) -> Result<ExcelUnitsPreview, CommandError> {
+  let mut wb = open_workbook_auto(&path)?;
+  let (sheet_name, range) = load_sheet_range(&mut wb, &config.sheet)?;
+
+  let cols = resolve_excel_columns(&range, &config)?;
+  let cells = CellReader::new(&mut wb, &sheet_name, &range, &config);
+
+  let mut preview = ExcelUnitsPreview::default();
+  for (i, row) in range.rows().enumerate() {
+    if i <= cols.header_idx { continue; }
+    preview.total_rows += 1;
+    if reached_max_units(preview.would_extract, config.max_units) {
+      preview.skipped_over_max += 1;
+      continue;
+    }
+    match excel_row_outcome(i, row, &cols, &config, &cells, preview.would_extract + 1) {
+      RowOutcome::Unit(_) => preview.would_extract += 1,
+      RowOutcome::Filtered => preview.skipped_filtered += 1,
+      RowOutcome::ErrorValue(_) => preview.skipped_error += 1,
+      RowOutcome::EmptyId => preview.skipped_empty_id += 1,
+      RowOutcome::EmptyBody => preview.skipped_empty_body += 1,
+      RowOutcome::TooShort => preview.skipped_too_short += 1,
+    }
+  }
+
+  Ok(preview)
+}
+
+#[tauri::command]
+fn preview_excel(path: String, sheet: String, limit: Option<usize>, header_scan_limit: Option<usize>) -> Result<ApiTable, CommandError> {
+  let limit = limit.unwrap_or(20);
+  let mut wb = open_workbook_auto(&path)?;
+  let (_, range) = load_sheet_range(&mut wb, &sheet)?;
+
+  let (header_idx, header) = detect_header_row(&range, header_scan_limit)?;
+
+  let columns = unique_column_names(header);
+  let rows = range.rows()
+    .skip(header_idx + 1)
+    .take(limit)
+    .map(|row| row_to_map(&columns, row))
+    .collect();
+
+  Ok(ApiTable { columns, rows, raw: None })
+}
+
+// Row maps are keyed by column name, so repeated headers get a " (n)" suffix
+fn unique_column_names(header: Vec<String>) -> Vec<String> {
+  let mut seen: HashMap<String, usize> = HashMap::new();
+  header.into_iter().map(|h| {
+    let n = seen.entry(h.clone()).or_insert(0);
+    *n += 1;
+    if *n == 1 { h } else { format!("{} ({})", h, n) }
+  }).collect()
+}
+
+fn row_to_map(columns: &[String], row: &[DataType]) -> HashMap<String, String> {
+  columns.iter().enumerate()
+    .map(|(j, c)| (c.clone(), row.get(j).and_then(cell_to_string).unwrap_or_default()))
+    .collect()
+}
+
+// Random data rows (after the header), in sheet order; see sample_lines for `seed`
+#[tauri::command]
+fn sample_excel_rows(
+  path: String,
+  sheet: String,
+  n: usize,
+  seed: Option<u64>,
+  header_scan_limit: Option<usize>,
+) -> Result<ApiTable, CommandError> {
+  let mut wb = open_workbook_auto(&path)?;
+  let (_, range) = load_sheet_range(&mut wb, &sheet)?;
+  let (header_idx, header) = detect_header_row(&range, header_scan_limit)?;
+
+  let columns = unique_column_names(header);
+  let mut rng = SplitMix64::new(seed);
+  let rows = reservoir_sample(range.rows().skip(header_idx + 1), n, &mut rng)
+    .into_iter()
+    .map(|(_, row)| row_to_map(&columns, row))
+    .collect();
+  Ok(ApiTable { columns, rows, raw: None })
+}
+
+fn cell_to_string(c: &DataType) -> Option<String> {
+  match c {
+    DataType::String(s) => Some(s.to_string()),
+    DataType::Float(f) => Some(if f.fract() == 0.0 { format!("{:.0}", f) } else { f.to_string() }),
+    DataType::Int(i) => Some(i.to_string()),
+    DataType::Bool(b) => Some(b.to_string()),
+    DataType::DateTimeIso(s) => Some(s.trim().to_string()),
+    DataType::DurationIso(s) => Some(iso_duration_to_hms(s).unwrap_or_else(|| s.trim().to_string())),
+    DataType::Empty => None,
+    _ => Some(c.to_string()),
+  }
+}
+
+// "PT10H30M5S" / "P1DT2H" -> "10:30:05" / "26:00:00". Years/months are ambiguous, so bail out.
+fn iso_duration_to_hms(s: &str) -> Option<String> {
+  let rest = s.trim().strip_prefix('P')?;
+  let mut secs: f64 = 0.0;
+  let mut num = String::new();
+  let mut in_time = false;
+  for ch in rest.chars() {
+    match ch {
+      'T' => in_time = true,
+      '0'..='9' | '.' | ',' => num.push(if ch == ',' { '.' } else { ch }),
+      unit => {
+        let v: f64 = num.parse().ok()?;
+        num.clear();
+        secs += match (in_time, unit) {
+          (false, 'W') => v * 7.0 * 86400.0,
+          (false, 'D') => v * 86400.0,
+          (true, 'H') => v * 3600.0,
+          (true, 'M') => v * 60.0,
+          (true, 'S') => v,
+          _ => return None,
+        };
+      }
+    }
+  }
+  if !num.is_empty() { return None; }
+  let total = secs.floor() as u64;
+  Some(format!("{:02}:{:02}:{:02}", total / 3600, (total % 3600) / 60, total % 60))
+}
+
+/* ---------- Regex blocks ---------- */
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegexConfig {
+  delimiter: String,
+  id_capture: Option<String>,
+  flags: Option<String>,
+  max_units: Option<usize>,
+  min_body_len: Option<usize>,
+  id_mode: Option<String>,
+  normalize_whitespace: Option<bool>,
+  keep_empty: Option<bool>,
+  id_fallback_template: Option<String>,
+  // Every match's group 1 (or the whole match) per block, in meta.captures
+  collect_captures: Option<String>,
+  id_case: Option<String>,
+}
+
+// JS-style flag letters: i = case-insensitive, m = multi-line, s = dot matches newline
+fn build_regex(pattern: &str, flags: Option<&str>) -> Result<regex::Regex, regex::Error> {
+  let mut builder = regex::RegexBuilder::new(pattern);
+  if let Some(f) = flags {
+    if f.contains('i') { builder.case_insensitive(true); }
+    if f.contains('m') { builder.multi_line(true); }
+    if f.contains('s') { builder.dot_matches_new_line(true); }
+  }
+  builder.build()
+}
+
+// The compiled regexes of a RegexConfig. Building this also rejects an unknown
+// idMode / idCase, so count_regex_blocks fails wherever extraction would.
+struct RegexPlan {
+  delim: regex::Regex,
+  id_re: Option<regex::Regex>,
+  captures_re: Option<regex::Regex>,
+}
+
+fn plan_regex_config(config: &RegexConfig) -> Result<RegexPlan, CommandError> {
+  let delim = build_regex(&config.delimiter, config.flags.as_deref())?;
+  let id_re = if let Some(idc) = &config.id_capture {
+    Some(build_regex(idc, config.flags.as_deref())?)
+  } else { None };
+  let captures_re = match config.collect_captures.as_deref() {
+    Some(c) if !c.trim().is_empty() => Some(build_regex(c, config.flags.as_deref())?),
+    _ => None,
+  };
+  apply_id_mode(&mut [], config.id_mode.as_deref())?;
+  apply_id_case(&mut [], config.id_case.as_deref())?;
+  Ok(RegexPlan { delim, id_re, captures_re })
+}
+
+// The body a trimmed block yields and whether it is a keepEmpty placeholder;
+// None when normalizing / minBodyLen drop the block
+fn regex_block_body(block: &str, config: &RegexConfig) -> Option<(String, bool)> {
+  let body = if config.normalize_whitespace.unwrap_or(false) { normalize_whitespace(block) } else { block.to_string() };
+  let placeholder = body.is_empty() && config.keep_empty.unwrap_or(false);
+  if !placeholder && (body.is_empty() || below_min_len(&body, config.min_body_len)) { return None; }
+  Some((body, placeholder))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn extract_regex_blocks(path: String, config: RegexConfig) -> Result<Vec<PromptUnit>, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let text = decode_text(&data);
+  let RegexPlan { delim, id_re, captures_re } = plan_regex_config(&config)?;
+
+  let mut units: Vec<PromptUnit> = Vec::new();
+  for (s, e) in regex_block_spans(&text, &delim) {
+    if reached_max_units(units.len(), config.max_units) { break; }
+    let raw = &text[s..e];
+    let block = raw.trim();
+    let Some((body, placeholder)) = regex_block_body(block, &config) else { continue; };
+    let id = if let Some(re) = &id_re {
+      re.captures(block).and_then(|c| c.get(1)).map(|m| m.as_str().to_string())
+        .unwrap_or_else(|| fallback_id(config.id_fallback_template.as_deref(), units.len()+1))
+    } else {
+      fallback_id(config.id_fallback_template.as_deref(), units.len()+1)
+    };
+    // Byte range of the trimmed block in the decoded text (text[start..end] is the
+    // body unless whitespace was normalized), which matches the file bytes for
+    // UTF-8 input without a BOM
+    let start = s + (raw.len() - raw.trim_start().len());
+    let mut meta = Map::new();
+    meta.insert("start".into(), Value::from(start));
+    meta.insert("end".into(), Value::from(start + block.len()));
+    if let Some(re) = &captures_re {
+      let found: Vec<Value> = re.captures_iter(block)
+        .filter_map(|c| c.get(1).or_else(|| c.get(0)))
+        .map(|m| Value::String(m.as_str().to_string()))
+        .collect();
+      meta.insert("captures".into(), Value::Array(found));
+    }
+    let meta = Some(Value::Object(meta));
+    let meta = if placeholder { with_skipped_reason(meta, "empty_body") } else { meta };
+    units.push(PromptUnit { id, body, meta });
+  }
+
+  apply_id_mode(&mut units, config.id_mode.as_deref())?;
+  apply_id_case(&mut units, config.id_case.as_deref())?;
+  tag_source_path(&mut units, &path);
+  tracing::info!(units = units.len(), "extracted units");
+  Ok(units)
+}
+
+// Byte spans between delimiter matches; no match at all → the whole text is one block
+fn regex_block_spans(text: &str, delim: &regex::Regex) -> Vec<(usize, usize)> {
+  let mut starts: Vec<usize> = delim.find_iter(text).map(|m| m.start()).collect();
+  if starts.is_empty() {
+    return vec![(0, text.len())];
+  }
+  starts.insert(0, 0);
+  starts.push(text.len());
+  starts.windows(2).filter(|w| w[1] > w[0]).map(|w| (w[0], w[1])).collect()
+}
+
+// Same config checks and block filtering as extract_regex_blocks, without building units
+#[tauri::command]
+fn count_regex_blocks(path: String, config: RegexConfig) -> Result<usize, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let text = decode_text(&data);
+  let plan = plan_regex_config(&config)?;
+
+  let mut count = 0;
+  for (s, e) in regex_block_spans(&text, &plan.delim) {
+    if reached_max_units(count, config.max_units) { break; }
+    if regex_block_body(text[s..e].trim(), &config).is_some() { count += 1; }
+  }
+  Ok(count)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DelimiterSuggestion {
+  label: String,
+  delimiter: String,   // ready to use as RegexConfig.delimiter
+  blocks: usize,
+}
+
+// Structural splitters tried by suggest_delimiters, most specific first
+const DELIMITER_CANDIDATES: &[(&str, &str)] = &[
+  ("Markdown headings", r"(?m)^#{1,6}[ \t]+\S"),
+  ("Horizontal rules", r"(?m)^[ \t]*(?:-{3,}|\*{3,}|_{3,})[ \t]*$"),
+  ("Numbered items", r"(?m)^[ \t]*\d+[.)][ \t]+\S"),
+];
+
+// Candidates that match at least once, ranked by the number of non-empty blocks
+// they would produce. Ties keep the most specific splitter first, so blank-line
+// paragraphs (which split almost any prose) lose to a structural match of equal count.
+#[tauri::command]
+fn suggest_delimiters(path: String) -> Result<Vec<DelimiterSuggestion>, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let text = decode_text(&data);
+  let count_blocks = |delim: &regex::Regex| {
+    regex_block_spans(&text, delim).into_iter().filter(|&(s, e)| !text[s..e].trim().is_empty()).count()
+  };
+
+  let mut out: Vec<DelimiterSuggestion> = Vec::new();
+  for (label, pattern) in DELIMITER_CANDIDATES {
+    let re = regex::Regex::new(pattern).expect("static delimiter regex");
+    if !re.is_match(&text) { continue; }
+    out.push(DelimiterSuggestion { label: label.to_string(), delimiter: pattern.to_string(), blocks: count_blocks(&re) });
+  }
+  let paragraphs = split_paragraphs(&text).len();
+  if paragraphs > 1 {
+    let pattern = r"\r?\n(?:[ \t]*\r?\n)+";
+    out.push(DelimiterSuggestion { label: "Blank-line paragraphs".into(), delimiter: pattern.into(), blocks: paragraphs });
+  }
+  // Stable, so equal counts stay in candidate order
+  out.sort_by_key(|s| std::cmp::Reverse(s.blocks));
+  Ok(out)
+}
+
+// id + body columns, plus one column per meta key seen on any unit
+fn units_to_table(units: &[PromptUnit]) -> ApiTable {
+  let mut meta_cols: BTreeSet<String> = BTreeSet::new();
+  for u in units {
+    if let Some(Value::Object(m)) = &u.meta {
+      for k in m.keys() { meta_cols.insert(k.clone()); }
+    }
+  }
+  meta_cols.remove("id");
+  meta_cols.remove("body");
+
+  let mut columns = vec!["id".to_string(), "body".to_string()];
+  columns.extend(meta_cols);
+
+  let rows = units.iter().map(|u| {
+    let mut r = HashMap::new();
+    r.insert("id".to_string(), u.id.clone());
+    r.insert("body".to_string(), u.body.clone());
+    for c in &columns[2..] {
+      let v = u.meta.as_ref().and_then(|m| m.get(c)).map(json_to_string).unwrap_or_default();
+      r.insert(c.clone(), v);
+    }
+    r
+  }).collect();
+
+  ApiTable { columns, rows, raw: None }
+}
+
+#[tauri::command]
+fn regex_blocks_as_table(path: String, config: RegexConfig) -> Result<ApiTable, CommandError> {
+  let units = extract_regex_blocks(path, config)?;
+  Ok(units_to_table(&units))
+}
+
+/* ---------- Blank-line paragraphs ---------- */
+
+// Paragraph breaks: a newline followed by one or more whitespace-only lines
+fn split_paragraphs(text: &str) -> Vec<&str> {
+  let re = regex::Regex::new(r"\r?\n(?:[ \t]*\r?\n)+").expect("static paragraph regex");
+  re.split(text).map(|p| p.trim()).filter(|p| !p.is_empty()).collect()
+}
+
+// Greedily packs paragraphs (joined by a blank line) into chunks of at most
+// `max_bytes`; a single paragraph larger than that becomes its own chunk
+fn pack_paragraphs(text: &str, max_bytes: usize) -> Vec<String> {
+  let mut chunks: Vec<String> = Vec::new();
+  let mut current = String::new();
+  for para in split_paragraphs(text) {
+    if !current.is_empty() && current.len() + 2 + para.len() > max_bytes {
+      chunks.push(std::mem::take(&mut current));
+    }
+    if !current.is_empty() { current.push_str("\n\n"); }
+    current.push_str(para);
+  }
+  if !current.is_empty() { chunks.push(current); }
+  chunks
+}
+
+// Fixed-size windows of `chunk_chars` characters, each starting `overlap_chars`
+// characters before the previous one ended. Sizes count chars, and slicing only
+// happens at char_indices boundaries, so multi-byte characters are never split.
+#[tauri::command]
+fn chunk_text(text: String, chunk_chars: usize, overlap_chars: Option<usize>) -> Result<Vec<String>, CommandError> {
+  let overlap = overlap_chars.unwrap_or(0);
+  if chunk_chars == 0 {
+    return Err(CommandError::Config("chunkChars must be greater than 0".into()));
+  }
+  if overlap >= chunk_chars {
+    return Err(CommandError::Config(format!("overlapChars ({}) must be less than chunkChars ({})", overlap, chunk_chars)));
+  }
+
+  // Byte offset of every char start, plus the end of the text
+  let bounds: Vec<usize> = text.char_indices().map(|(i, _)| i).chain(std::iter::once(text.len())).collect();
+  let total = bounds.len() - 1;
+  let mut chunks = Vec::new();
+  let mut start = 0;
+  while start < total {
+    let end = (start + chunk_chars).min(total);
+    chunks.push(text[bounds[start]..bounds[end]].to_string());
+    if end == total { break; }
+    start = end - overlap;
+  }
+  Ok(chunks)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn extract_paragraphs(path: String, min_len: Option<usize>) -> Result<Vec<PromptUnit>, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let text = decode_text(&data);
+
+  let mut units: Vec<PromptUnit> = Vec::new();
+  for para in split_paragraphs(&text) {
+    if below_min_len(para, min_len) { continue; }
+    units.push(PromptUnit { id: format!("{}", units.len()+1), body: para.to_string(), meta: None });
+  }
+  tag_source_path(&mut units, &path);
+  tracing::info!(units = units.len(), "extracted units");
+  Ok(units)
+}
+
+/* ---------- Markdown documents with YAML front matter ---------- */
+
+fn is_front_matter_fence(line: &str) -> bool {
+  line.trim_end() == "---"
+}
+
+// The lines between two fences, if they parse as a YAML mapping
+fn parse_front_matter(lines: &[&str]) -> Option<Map<String, Value>> {
+  let yaml = lines.join("\n");
+  if yaml.trim().is_empty() { return None; }
+  match serde_yaml::from_str::<Value>(&yaml) {
+    Ok(Value::Object(m)) => Some(m),
+    _ => None,
+  }
+}
+
+// (front matter, body) per document. A `---` line opens a new document only when it
+// starts the file or follows a blank line, and a closing `---` follows with a YAML
+// mapping in between; any other `---` (e.g. a horizontal rule, or a "Note: ..."
+// callout set off by rules within a paragraph) stays in the body. Text before the
+// first front matter is a document of its own, with empty front matter, when it is
+// not blank.
+fn split_markdown_docs(text: &str) -> Vec<(Map<String, Value>, String)> {
+  let lines: Vec<&str> = text.lines().collect();
+  let mut docs = Vec::new();
+  let mut meta = Map::new();
+  let mut body: Vec<&str> = Vec::new();
+  let mut i = 0;
+  while i < lines.len() {
+    if is_front_matter_fence(lines[i]) && (i == 0 || lines[i - 1].trim().is_empty()) {
+      let close = lines[i + 1..].iter().position(|l| is_front_matter_fence(l)).map(|p| i + 1 + p);
+      if let Some((close, next_meta)) = close.and_then(|c| parse_front_matter(&lines[i + 1..c]).map(|m| (c, m))) {
+        let prev = body.join("\n").trim().to_string();
+        if !meta.is_empty() || !prev.is_empty() {
+          docs.push((std::mem::take(&mut meta), prev));
+        }
+        meta = next_meta;
+        body.clear();
+        i = close + 1;
+        continue;
+      }
+    }
+    body.push(lines[i]);
+    i += 1;
+  }
+  let last = body.join("\n").trim().to_string();
+  if !meta.is_empty() || !last.is_empty() {
+    docs.push((meta, last));
+  }
+  docs
+}
+
+// One unit per document: front matter as meta, `id_field` (default "id") as the id
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn extract_markdown_docs(path: String, id_field: Option<String>) -> Result<Vec<PromptUnit>, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let text = decode_text(&data);
+  let id_field = id_field.unwrap_or_else(|| "id".to_string());
+
+  let mut units: Vec<PromptUnit> = Vec::new();
+  for (meta, body) in split_markdown_docs(&text) {
+    let id = meta.get(&id_field)
+      .map(|v| json_to_string(v).trim().to_string())
+      .filter(|id| !id.is_empty())
+      .unwrap_or_else(|| fallback_id(None, units.len() + 1));
+    let meta = if meta.is_empty() { None } else { Some(Value::Object(meta)) };
+    units.push(PromptUnit { id, body, meta });
+  }
+  tag_source_path(&mut units, &path);
+  tracing::info!(units = units.len(), "extracted units");
+  Ok(units)
+}
+
+/* ---------- Markdown fenced code blocks ---------- */
+
+// "```rust" / "~~~ python title=x" (up to 3 spaces of indent) -> (fence char, length, info)
+fn opening_fence(line: &str) -> Option<(char, usize, &str)> {
+  let indent = line.len() - line.trim_start_matches(' ').len();
+  if indent > 3 { return None; }
+  let rest = &line[indent..];
+  let ch = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+  let len = rest.len() - rest.trim_start_matches(ch).len();
+  if len < 3 { return None; }
+  let info = rest[len..].trim();
+  // Backtick fences may not have backticks in their info string
+  if ch == '`' && info.contains('`') { return None; }
+  Some((ch, len, info))
+}
+
+fn is_closing_fence(line: &str, ch: char, len: usize) -> bool {
+  let t = line.trim();
+  line.len() - line.trim_start_matches(' ').len() <= 3
+    && t.len() >= len
+    && t.chars().all(|c| c == ch)
+}
+
+// One unit per fenced block: the code as body, the info string's first word in
+// meta.language ("" when untagged) and the opening fence's 1-based meta.line.
+// An unclosed fence runs to the end of the file, as in CommonMark.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn extract_code_blocks(path: String, language_filter: Option<String>) -> Result<Vec<PromptUnit>, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let text = decode_text(&data);
+  let filter = language_filter.map(|f| f.trim().to_lowercase()).filter(|f| !f.is_empty());
+
+  let mut units: Vec<PromptUnit> = Vec::new();
+  let mut lines = text.lines().enumerate();
+  while let Some((start, line)) = lines.next() {
+    let Some((ch, len, info)) = opening_fence(line) else { continue; };
+    let language = info.split_whitespace().next().unwrap_or("").to_string();
+    let mut code: Vec<&str> = Vec::new();
+    for (_, l) in lines.by_ref() {
+      if is_closing_fence(l, ch, len) { break; }
+      code.push(l);
+    }
+    if filter.as_ref().is_some_and(|f| *f != language.to_lowercase()) { continue; }
+    units.push(PromptUnit {
+      id: fallback_id(None, units.len() + 1),
+      body: code.join("\n"),
+      meta: Some(serde_json::json!({ "language": language, "line": start + 1 })),
+    });
+  }
+  tag_source_path(&mut units, &path);
+  tracing::info!(units = units.len(), "extracted units");
+  Ok(units)
+}
+
+/* ---------- JSON / JSONL records ---------- */
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonConfig {
+  id_field: String,
+  body_fields: Vec<String>,
+  array_path: Option<String>,    // dotted, e.g. "data.items" or "results.0.rows"
+  keep_empty: Option<bool>,
+  id_case: Option<String>,
+}
+
+// Follow a dotted path through objects (by key) and arrays (by index)
+fn json_path<'a>(v: &'a Value, path: &str) -> Option<&'a Value> {
+  path.split('.').filter(|seg| !seg.is_empty()).try_fold(v, |cur, seg| match cur {
+    Value::Object(m) => m.get(seg),
+    Value::Array(a) => seg.parse::<usize>().ok().and_then(|i| a.get(i)),
+    _ => None,
+  })
+}
+
+// A whole-file JSON document, or one JSON value per non-empty line (JSONL)
+fn parse_json_records(text: &str, array_path: Option<&str>) -> Result<Vec<Value>, CommandError> {
+  let doc: Value = match serde_json::from_str(text) {
+    Ok(v) => v,
+    Err(_) => {
+      let mut lines = Vec::new();
+      for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() { continue; }
+        let v = serde_json::from_str(line)
+          .map_err(|e| CommandError::Parse(format!("Line {}: {}", n + 1, e)))?;
+        lines.push(v);
+      }
+      return Ok(lines);
+    }
+  };
+
+  if let Some(ap) = array_path.filter(|p| !p.trim().is_empty()) {
+    return json_path(&doc, ap)
+      .and_then(|v| v.as_array().cloned())
+      .ok_or_else(|| CommandError::Config(format!("No array at path: {}", ap)));
+  }
+  if let Some(arr) = doc.as_array() {
+    return Ok(arr.clone());
+  }
+  // A lone object (e.g. a one-line JSONL file) is a single record; wrapped
+  // arrays are reached with arrayPath
+  Ok(vec![doc])
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn extract_json_units(path: String, config: JsonConfig) -> Result<Vec<PromptUnit>, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let text = decode_text(&data);
+  let records = parse_json_records(&text, config.array_path.as_deref())?;
+
+  let mut units: Vec<PromptUnit> = Vec::new();
+  for (i, rec) in records.iter().enumerate() {
+    let id = json_path(rec, &config.id_field).map(json_to_string).unwrap_or_default().trim().to_string();
+    if id.is_empty() { continue; }
+
+    let parts: Vec<String> = config.body_fields.iter()
+      .filter_map(|f| json_path(rec, f).map(json_to_string))
+      .map(|v| v.trim().to_string())
+      .filter(|v| !v.is_empty())
+      .collect();
+    let body = parts.join("\n");
+    let placeholder = body.is_empty() && config.keep_empty.unwrap_or(false);
+    if body.is_empty() && !placeholder { continue; }
+
+    let meta = Some(serde_json::json!({ "recordIndex": i }));
+    let meta = if placeholder { with_skipped_reason(meta, "empty_body") } else { meta };
+    units.push(PromptUnit { id, body, meta });
+  }
+
+  apply_id_case(&mut units, config.id_case.as_deref())?;
+  tracing::info!(units = units.len(), "extracted units");
+  Ok(units)
+}
+
+/* ---------- HTML (CSS) blocks — best practice: parse DOM, not regex ---------- */
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HtmlConfig {
+  // May be a comma-separated group (".card, .tile"). scraper walks the DOM once
+  // and tests the whole group per element, so items come back in document order
+  // and an element matching several selectors is returned only once.
+  item_selector: String,
+  // Tried only when item_selector matches nothing; when set, units record the
+  // selector that produced them in meta.selector
+  fallback_item_selector: Option<String>,
+  id_selector: Option<String>,
+  id_attr: Option<String>,       // defaults to "id"
+  desc_selector: Option<String>,
+  max_units: Option<usize>,
+  min_body_len: Option<usize>,
+  id_mode: Option<String>,
+  normalize_whitespace: Option<bool>,
+  include_html: Option<bool>,    // inner HTML of each item in meta.html
+  keep_empty: Option<bool>,
+  id_fallback_template: Option<String>,
+  id_case: Option<String>,
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn extract_html_blocks(path: String, config: HtmlConfig) -> Result<Vec<PromptUnit>, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let mut units = html_units_from_doc(&Html::parse_document(&decode_text(&data)), &config)?;
+  tag_source_path(&mut units, &path);
+  tracing::info!(units = units.len(), "extracted units");
+  Ok(units)
+}
+
+fn html_units_from_doc(doc: &Html, config: &HtmlConfig) -> Result<Vec<PromptUnit>, CommandError> {
+  let item_sel = Selector::parse(&config.item_selector)
+    .map_err(|_| CommandError::Config("Invalid itemSelector".into()))?;
+  let fallback_sel = match &config.fallback_item_selector {
+    Some(s) if !s.trim().is_empty() => Some(Selector::parse(s).map_err(|_| CommandError::Config("Invalid fallbackItemSelector".into()))?),
+    _ => None
+  };
+  let (item_sel, selector_used) = match fallback_sel {
+    Some(fb) if doc.select(&item_sel).next().is_none() => (fb, config.fallback_item_selector.as_deref()),
+    Some(_) => (item_sel, Some(config.item_selector.as_str())),
+    None => (item_sel, None),
+  };
+
+  let id_sel = match &config.id_selector {
+    Some(s) if !s.trim().is_empty() => Some(Selector::parse(s).map_err(|_| CommandError::Config("Invalid idSelector".into()))?),
+    _ => None
+  };
+  let desc_sel = match &config.desc_selector {
+    Some(s) if !s.trim().is_empty() => Some(Selector::parse(s).map_err(|_| CommandError::Config("Invalid descSelector".into()))?),
+    _ => None
+  };
+  let id_attr = config.id_attr.as_deref().unwrap_or("id");
+
+  let mut units: Vec<PromptUnit> = Vec::new();
+
+  for (i, el) in doc.select(&item_sel).enumerate() {
+    if reached_max_units(units.len(), config.max_units) { break; }
+    // resolve id
+    let id = if let Some(sel) = &id_sel {
+      if let Some(node) = el.select(sel).next() {
+        if let Some(v) = node.value().attr(id_attr) {
+          v.to_string()
+        } else {
+          let text = node.text().collect::<String>().trim().to_string();
+          if text.is_empty() { fallback_id(config.id_fallback_template.as_deref(), i+1) } else { text }
+        }
+      } else {
+        fallback_id(config.id_fallback_template.as_deref(), i+1)
+      }
+    } else {
+      if let Some(v) = el.value().attr(id_attr) {
+        v.to_string()
+      } else {
+        fallback_id(config.id_fallback_template.as_deref(), i+1)
+      }
+    };
+
+    // resolve description text
+    let body = if let Some(dsel) = &desc_sel {
+      let mut buf = String::new();
+      for n in el.select(dsel) {
+        let t = n.text().collect::<String>();
+        if !t.trim().is_empty() {
+          if !buf.is_empty() { buf.push('\n'); }
+          buf.push_str(t.trim());
+        }
+      }
+      if buf.is_empty() {
+        // fallback to full item text
+        el.text().collect::<String>().trim().to_string()
+      } else { buf }
+    } else {
+      el.text().collect::<String>().trim().to_string()
+    };
+
+    let body = if config.normalize_whitespace.unwrap_or(false) { normalize_whitespace(&body) } else { body };
+    let placeholder = body.is_empty() && config.keep_empty.unwrap_or(false);
+    if !placeholder && (body.is_empty() || below_min_len(&body, config.min_body_len)) { continue; }
+
+    // Position among all elements the item selector matched (skipped items leave
+    // gaps). html5ever keeps no source offsets, so this is the only locator.
+    let mut meta = Map::new();
+    meta.insert("domIndex".into(), Value::from(i));
+    if placeholder {
+      meta.insert("skippedReason".into(), Value::String("empty_body".into()));
+    }
+    if let Some(sel) = selector_used {
+      meta.insert("selector".into(), Value::String(sel.to_string()));
+    }
+    if config.include_html.unwrap_or(false) {
+      meta.insert("html".into(), Value::String(el.inner_html()));
+    }
+    units.push(PromptUnit { id, body, meta: Some(Value::Object(meta)) });
+  }
+
+  apply_id_mode(&mut units, config.id_mode.as_deref())?;
+  apply_id_case(&mut units, config.id_case.as_deref())?;
+  Ok(units)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SelectorValidation {
+  valid: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<String>,
+}
+
+// Lets the frontend check selector fields as they are typed
+#[tauri::command]
+fn validate_selector(selector: String) -> Result<SelectorValidation, CommandError> {
+  if selector.trim().is_empty() {
+    return Ok(SelectorValidation { valid: false, error: Some("Selector is empty".into()) });
+  }
+  Ok(match Selector::parse(&selector) {
+    Ok(_) => SelectorValidation { valid: true, error: None },
+    Err(e) => SelectorValidation { valid: false, error: Some(e.to_string()) },
+  })
+}
+
+/* ---------- HTML tables ---------- */
+
+// Rows of `table` itself, skipping rows of tables nested inside it
+fn own_table_rows<'a>(table: scraper::ElementRef<'a>, tr_sel: &Selector) -> Vec<scraper::ElementRef<'a>> {
+  table.select(tr_sel)
+    .filter(|tr| {
+      tr.ancestors()
+        .find(|n| n.value().as_element().is_some_and(|e| e.name() == "table"))
+        .is_some_and(|t| t.id() == table.id())
+    })
+    .collect()
+}
+
+// Cell texts of a row; a colspan of N is padded with N-1 empty cells
+fn table_row_cells(tr: scraper::ElementRef, cell_sel: &Selector) -> Vec<String> {
+  let mut cells = Vec::new();
+  for cell in tr.select(cell_sel).filter(|c| c.parent().map(|p| p.id()) == Some(tr.id())) {
+    cells.push(cell.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" "));
+    let span: usize = cell.value().attr("colspan").and_then(|v| v.trim().parse().ok()).unwrap_or(1);
+    cells.extend(std::iter::repeat_n(String::new(), span.clamp(1, 1000) - 1));
+  }
+  cells
+}
+
+fn html_table_to_api_table(table: scraper::ElementRef, tr_sel: &Selector, cell_sel: &Selector) -> ApiTable {
+  let rows = own_table_rows(table, tr_sel);
+  // Header: the first row inside <thead>, else the first row
+  let header_pos = rows.iter()
+    .position(|tr| tr.parent().and_then(scraper::ElementRef::wrap).is_some_and(|p| p.value().name() == "thead"))
+    .unwrap_or(0);
+
+  let mut columns: Vec<String> = Vec::new();
+  let mut seen: HashSet<String> = HashSet::new();
+  let mut push_column = |columns: &mut Vec<String>, name: &str| {
+    let base = if name.is_empty() { format!("col{}", columns.len() + 1) } else { name.to_string() };
+    let mut unique = base.clone();
+    let mut n = 2;
+    while !seen.insert(unique.clone()) {
+      unique = format!("{}_{}", base, n);
+      n += 1;
+    }
+    columns.push(unique);
+  };
+  if let Some(header) = rows.get(header_pos) {
+    for name in table_row_cells(*header, cell_sel) {
+      push_column(&mut columns, &name);
+    }
+  }
+
+  let mut out_rows: Vec<HashMap<String, String>> = Vec::new();
+  for (i, tr) in rows.iter().enumerate() {
+    if i <= header_pos { continue; }
+    let cells = table_row_cells(*tr, cell_sel);
+    if cells.iter().all(|c| c.is_empty()) { continue; }
+    while columns.len() < cells.len() {
+      push_column(&mut columns, "");
+    }
+    let mut row: HashMap<String, String> = columns.iter().map(|c| (c.clone(), String::new())).collect();
+    for (c, v) in columns.iter().zip(cells) {
+      row.insert(c.clone(), v);
+    }
+    out_rows.push(row);
+  }
+  // Earlier rows get any columns added by wider later rows
+  for row in out_rows.iter_mut() {
+    for c in &columns {
+      row.entry(c.clone()).or_default();
+    }
+  }
+  ApiTable { columns, rows: out_rows, raw: None }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn extract_html_tables(path: String, table_index: Option<usize>) -> Result<Vec<ApiTable>, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let doc = Html::parse_document(&decode_text(&data));
+  let table_sel = Selector::parse("table").expect("static selector");
+  let tr_sel = Selector::parse("tr").expect("static selector");
+  let cell_sel = Selector::parse("th, td").expect("static selector");
+
+  let tables: Vec<scraper::ElementRef> = doc.select(&table_sel).collect();
+  match table_index {
+    Some(i) => {
+      let table = tables.get(i)
+        .ok_or_else(|| CommandError::Config(format!("Table index {} out of range ({} tables)", i, tables.len())))?;
+      Ok(vec![html_table_to_api_table(*table, &tr_sel, &cell_sel)])
+    }
+    None => Ok(tables.iter().map(|t| html_table_to_api_table(*t, &tr_sel, &cell_sel)).collect()),
+  }
+}
+
+/* ---------- Whole-document HTML text ---------- */
+
+const HTML_SKIP_TAGS: &[&str] = &["script", "style", "noscript", "template", "head"];
+const HTML_BLOCK_TAGS: &[&str] = &[
+  "address", "article", "aside", "blockquote", "br", "dd", "div", "dl", "dt", "figcaption", "figure",
+  "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "main", "nav", "ol", "p", "pre",
+  "section", "table", "tr", "ul",
+];
+
+// Visible text of an element. Source whitespace collapses as a browser would;
+// block-level elements start and end on their own line
+fn push_visible_text(el: scraper::ElementRef, out: &mut String) {
+  for child in el.children() {
+    if let Some(child_el) = scraper::ElementRef::wrap(child) {
+      let name = child_el.value().name();
+      if HTML_SKIP_TAGS.contains(&name) { continue; }
+      let block = HTML_BLOCK_TAGS.contains(&name);
+      if block { out.push('\n'); }
+      push_visible_text(child_el, out);
+      if block { out.push('\n'); }
+    } else if let scraper::Node::Text(t) = child.value() {
+      out.extend(t.chars().map(|c| if c.is_whitespace() { ' ' } else { c }));
+    }
+  }
+}
+
+#[tauri::command]
+fn html_to_text(path: String, preserve_newlines: Option<bool>) -> Result<String, CommandError> {
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let doc = Html::parse_document(&decode_text(&data));
+  let mut raw = String::new();
+  push_visible_text(doc.root_element(), &mut raw);
+
+  if preserve_newlines.unwrap_or(true) {
+    let lines: Vec<String> = raw.lines()
+      .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+      .filter(|l| !l.is_empty())
+      .collect();
+    Ok(lines.join("\n"))
+  } else {
+    Ok(raw.split_whitespace().collect::<Vec<_>>().join(" "))
+  }
+}
+
+// ADD this new command (async)
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(endpoint = %endpoint, path = %path), err)]
+async fn extract_api_units(
+  endpoint: String,
+  path: String,
+  which: String,                       // "items" | "notes"
+  headers: Option<HashMap<String, String>>,
+  id_mode: Option<String>,
+  proxy: Option<String>,
+) -> Result<Vec<PromptUnit>, CommandError> {
+  // 1) Read the selected file
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let html_text = String::from_utf8_lossy(&data).into_owned();
+
+  // 2) POST to the endpoint as JSON
+  let client = with_proxy(reqwest::Client::builder(), proxy.as_deref())?
+    .gzip(true)
+    .deflate(true)
+    .brotli(true)
+    .user_agent("rag-util/1.0")
+    .build()?;
+
+  let mut req = client
+    .post(&endpoint)
+    .header(reqwest::header::CONTENT_TYPE, "application/json");
+
+  if let Some(h) = headers.as_ref() {
+    for (k,v) in h {
+      req = req.header(k, v);
+    }
+  }
+
+  let payload = serde_json::json!({ "html": html_text });
+  let resp = send_respecting_retry_after(req.json(&payload)).await?;
+  tracing::debug!(url = %endpoint, status = %resp.status(), "POST");
+  if !resp.status().is_success() {
+    return Err(CommandError::Network(format!("API error {} from {}", resp.status(), endpoint)));
+  }
+
+  // 3) Accept several response shapes
+  //    - array of objects
+  //    - {items:[...]} or {notes:[...]}
+  //    Each object should have {code, items_text? / notes_text?}
+  let v: Value = read_json_response(resp, DEFAULT_MAX_RESPONSE_BYTES).await?;
+  let list: Vec<Value> = if let Some(arr) = v.as_array() {
+    arr.clone()
+  } else if let Some(arr) = v.get("items").and_then(|x| x.as_array()) {
+    arr.clone()
+  } else if let Some(arr) = v.get("notes").and_then(|x| x.as_array()) {
+    arr.clone()
+  } else {
+    vec![v]
+  };
+
+  let take_key = if which.to_lowercase().starts_with('n') { "notes_text" } else { "items_text" };
+  let mut out: Vec<PromptUnit> = Vec::new();
+
+  for item in list {
+    let code = item.get("code").and_then(|x| x.as_str()).unwrap_or("").trim().to_string();
+    let body = item.get(take_key).and_then(|x| x.as_str()).unwrap_or("").trim().to_string();
+    if !code.is_empty() && !body.is_empty() {
+      out.push(PromptUnit { id: code, body, meta: None });
+    }
+  }
+
+  apply_id_mode(&mut out, id_mode.as_deref())?;
+  tracing::info!(units = out.len(), "extracted API units");
+  Ok(out)
+}
+
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 50 * 1024 * 1024;
+
+// Read a body chunk by chunk and bail out as soon as it grows past `max`
+async fn read_body_capped(mut resp: reqwest::Response, max: usize) -> Result<Vec<u8>, CommandError> {
+  let url = resp.url().to_string();
+  let too_large = || CommandError::Network(format!("Response from {} exceeds the {} byte limit", url, max));
+  if resp.content_length().is_some_and(|len| len > max as u64) {
+    return Err(too_large());
+  }
+  let mut buf: Vec<u8> = Vec::new();
+  while let Some(chunk) = resp.chunk().await
+    .map_err(|e| CommandError::Network(format!("Could not decode response body from {}: {}", url, e)))?
+  {
+    if buf.len() + chunk.len() > max {
+      return Err(too_large());
+    }
+    buf.extend_from_slice(&chunk);
+  }
+  Ok(buf)
+}
+
+// Rate-limited (429) calls are retried up to this many times, each after the
+// server's Retry-After delay (1s when absent), never waiting longer than the cap
+const MAX_RATE_LIMIT_RETRIES: usize = 3;
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+async fn send_respecting_retry_after(req: reqwest::RequestBuilder) -> Result<reqwest::Response, reqwest::Error> {
+  let mut retries = 0;
+  loop {
+    // Streaming bodies cannot be replayed, so those get a single attempt
+    let Some(attempt) = req.try_clone() else { return req.send().await; };
+    let resp = attempt.send().await?;
+    if resp.status() != reqwest::StatusCode::TOO_MANY_REQUESTS || retries >= MAX_RATE_LIMIT_RETRIES {
+      return Ok(resp);
+    }
+    let wait = resp.headers().get(reqwest::header::RETRY_AFTER)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| parse_retry_after(v, SystemTime::now()))
+      .unwrap_or(Duration::from_secs(1))
+      .min(MAX_RETRY_AFTER);
+    tracing::debug!(url = %resp.url(), wait_ms = wait.as_millis() as u64, "rate limited; retrying");
+    tokio::time::sleep(wait).await;
+    retries += 1;
+  }
+}
+
+// Retry-After is either delay-seconds ("120") or an HTTP-date; past dates mean "now"
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+  let value = value.trim();
+  if let Ok(secs) = value.parse::<u64>() {
+    return Some(Duration::from_secs(secs));
+  }
+  let at = parse_http_date(value)?;
+  Some(at.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+// IMF-fixdate only ("Wed, 21 Oct 2015 07:28:00 GMT"), the form servers must send
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+  const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+  let parts: Vec<&str> = value.split_whitespace().collect();
+  if parts.len() != 6 || parts[5] != "GMT" { return None; }
+  let day: i64 = parts[1].parse().ok()?;
+  let month = MONTHS.iter().position(|m| *m == parts[2])? as i64 + 1;
+  let year: i64 = parts[3].parse().ok()?;
+  let hms: Vec<i64> = parts[4].split(':').map(|x| x.parse().ok()).collect::<Option<_>>()?;
+  let [h, m, s] = hms[..] else { return None; };
+
+  // Days since 1970-01-01 for a proleptic Gregorian date (Hinnant's days_from_civil)
+  let y = if month <= 2 { year - 1 } else { year };
+  let era = y.div_euclid(400);
+  let yoe = y - era * 400;
+  let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+  let days = era * 146_097 + doe - 719_468;
+
+  let secs = u64::try_from(days * 86_400 + h * 3600 + m * 60 + s).ok()?;
+  Some(std::time::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+// Parse a response body as JSON; on failure report the content-type and the
+// start of the body, since error pages often come back as HTML with a 200.
+async fn read_json_response(resp: reqwest::Response, max_bytes: usize) -> Result<Value, CommandError> {
+  let content_type = resp.headers()
+    .get(reqwest::header::CONTENT_TYPE)
+    .and_then(|v| v.to_str().ok())
+    .unwrap_or("unknown")
+    .to_string();
+  let bytes = read_body_capped(resp, max_bytes).await?;
+  let text = String::from_utf8_lossy(&bytes);
+  serde_json::from_str(&text).map_err(|e| {
+    let snippet: String = text.chars().take(200).collect();
+    CommandError::Parse(format!("Response is not valid JSON ({}; content-type: {}). Body starts with: {}", e, content_type, snippet))
+  })
+}
+
+fn json_to_string(v: &Value) -> String {
+  match v {
+    Value::Null => "".into(),
+    Value::Bool(b) => b.to_string(),
+    Value::Number(n) => n.to_string(),
+    Value::String(s) => s.clone(),
+    other => serde_json::to_string(other).unwrap_or_default(),
+  }
+}
+
+// Find an array of objects in common shapes: top-level array,
+// or under items/rows/data/result/notes/records, else first array-of-objects found.
+fn find_array_of_objects(v: &Value) -> Option<Vec<Map<String, Value>>> {
+  if let Some(arr) = v.as_array() {
+    let objs: Vec<_> = arr.iter().filter_map(|x| x.as_object().cloned()).collect();
+    if !objs.is_empty() { return Some(objs); }
+  }
+  if let Some(obj) = v.as_object() {
+    for key in ["items","rows","data","result","notes","records"] {
+      if let Some(val) = obj.get(key) {
+        if let Some(arr) = val.as_array() {
+          let objs: Vec<_> = arr.iter().filter_map(|x| x.as_object().cloned()).collect();
+          if !objs.is_empty() { return Some(objs); }
+        }
+      }
+    }
+    for (_k, val) in obj {
+      if let Some(arr) = val.as_array() {
+        let objs: Vec<_> = arr.iter().filter_map(|x| x.as_object().cloned()).collect();
+        if !objs.is_empty() { return Some(objs); }
+      }
+    }
+  }
+  None
+}
+
+const PAYLOAD_CONTENT_PLACEHOLDER: &str = "__CONTENT__";
+
+// `{ data: content }` by default; with a template, every "__CONTENT__" inside
+// its string values (at any depth) is replaced by the content
+fn api_payload(template: Option<&Value>, content: &str) -> Value {
+  fn fill(v: &Value, content: &str) -> Value {
+    match v {
+      Value::String(s) => Value::String(s.replace(PAYLOAD_CONTENT_PLACEHOLDER, content)),
+      Value::Array(a) => Value::Array(a.iter().map(|x| fill(x, content)).collect()),
+      Value::Object(m) => Value::Object(m.iter().map(|(k, x)| (k.clone(), fill(x, content))).collect()),
+      other => other.clone(),
+    }
+  }
+  match template {
+    Some(t) => fill(t, content),
+    None => serde_json::json!({ "data": content }),
+  }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(endpoint = %endpoint, path = %path), err)]
+async fn fetch_api_table(
+  endpoint: String,
+  path: String,
+  max_response_bytes: Option<usize>,
+  proxy: Option<String>,
+  payload_template: Option<Value>,
+  include_raw: Option<bool>,
+) -> Result<ApiTable, CommandError> {
+  let max_response = max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+  let data = std::fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let html_text = String::from_utf8_lossy(&data).into_owned();
+
+  let client = with_proxy(reqwest::Client::builder(), proxy.as_deref())?
+    .gzip(true)
+    .deflate(true)
+    .brotli(true)
+    .user_agent(BROWSER_USER_AGENT)
+    .build()?;
+
+  let req = client
+    .post(&endpoint)
+    .header(reqwest::header::CONTENT_TYPE, "application/json")
+    .json(&api_payload(payload_template.as_ref(), &html_text));
+  let resp = send_respecting_retry_after(req).await?;
+  tracing::debug!(url = %endpoint, status = %resp.status(), "POST");
+
+  if !resp.status().is_success() {
+    return Err(CommandError::Network(format!("API error {} from {}", resp.status(), endpoint)));
+  }
+
+  let v: Value = read_json_response(resp, max_response).await?;
+  let objs = find_array_of_objects(&v)
+    .ok_or_else(|| CommandError::Parse("No array of objects in API response".into()))?;
+
+  let mut cols: BTreeSet<String> = BTreeSet::new();
+  for o in &objs { for k in o.keys() { cols.insert(k.clone()); } }
+  let columns: Vec<String> = cols.into_iter().collect();
+
+  let mut rows: Vec<HashMap<String, String>> = Vec::new();
+  for o in objs {
+    let mut r = HashMap::new();
+    for c in &columns {
+      let s = o.get(c).map(json_to_string).unwrap_or_default();
+      r.insert(c.clone(), s);
+    }
+    rows.push(r);
+  }
+
+  // Off by default: the raw response can be much larger than the table
+  let raw = if include_raw.unwrap_or(false) { Some(v) } else { None };
+  Ok(ApiTable { columns, rows, raw })
+}
+
+// Column union in first-seen order; every row gets every column ("" when absent)
+#[tauri::command]
+fn merge_tables(tables: Vec<ApiTable>) -> Result<ApiTable, CommandError> {
+  let mut columns: Vec<String> = Vec::new();
+  let mut seen: HashSet<String> = HashSet::new();
+  for c in tables.iter().flat_map(|t| t.columns.iter()) {
+    if seen.insert(c.clone()) {
+      columns.push(c.clone());
+    }
+  }
+
+  let rows = tables.into_iter()
+    .flat_map(|t| t.rows)
+    .map(|mut r| {
+      for c in &columns {
+        r.entry(c.clone()).or_default();
+      }
+      r
+    })
+    .collect();
+  Ok(ApiTable { columns, rows, raw: None })
+}
+
+#[tauri::command]
+fn table_to_units(
+  table: ApiTable,
+  id_column: String,
+  body_columns: Vec<String>,
+  separator: Option<String>,
+  id_fallback_template: Option<String>,
+) -> Result<Vec<PromptUnit>, CommandError> {
+  let sep = separator.unwrap_or_else(|| "\n".to_string());
+  let mut units: Vec<PromptUnit> = Vec::new();
+
+  for (i, row) in table.rows.iter().enumerate() {
+    let parts: Vec<&str> = body_columns.iter()
+      .filter_map(|c| row.get(c))
+      .map(|v| v.trim())
+      .filter(|v| !v.is_empty())
+      .collect();
+    let body = parts.join(&sep);
+    if body.is_empty() { continue; }
+
+    // Rows without an id value fall back to their 1-based row number
+    let id = row.get(&id_column).map(|v| v.trim()).filter(|v| !v.is_empty())
+      .map(|v| v.to_string())
+      .unwrap_or_else(|| fallback_id(id_fallback_template.as_deref(), i+1));
+    units.push(PromptUnit { id, body, meta: Some(serde_json::json!({ "rowIndex": i })) });
+  }
+
+  Ok(units)
+}
+
+// Sanitized "base.ext", or the first free "base--2.ext", "base--3.ext", ...
+// (an empty base becomes "chunk")
+fn unique_file_name(base: &str, ext: &str, is_taken: impl Fn(&str) -> bool) -> Result<String, CommandError> {
+  let ext_sanitized = sanitize_for_filename(ext.trim_matches('.'));
+  let mut base_sanitized = sanitize_for_filename(base);
+  if base_sanitized.is_empty() {
+    base_sanitized = "chunk".to_string();
+  }
+
+  let mut attempt: usize = 1;
+  loop {
+    let candidate = if attempt == 1 {
+      format!("{}.{}", base_sanitized, ext_sanitized)
+    } else {
+      format!("{}--{}.{}", base_sanitized, attempt, ext_sanitized)
+    };
+    if !is_taken(&candidate) {
+      return Ok(candidate);
+    }
+    attempt += 1;
+    if attempt > 9999 {
+      return Err(CommandError::Io("Failed to create a unique filename (too many conflicts)".into()));
+    }
+  }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlannedFilename {
+  id: String,
+  filename: String,
+  // True when the plain name was taken and a --N suffix was added
+  collision: bool,
+}
+
+// The names saving each unit (base = id) would get, without writing anything.
+// Earlier units claim names first; with `dir`, files already there count as taken.
+#[tauri::command]
+fn preview_filenames(units: Vec<PromptUnit>, ext: Option<String>, dir: Option<String>) -> Result<Vec<PlannedFilename>, CommandError> {
+  let ext = ext.unwrap_or_else(|| "md".to_string());
+  let dir_path = dir.map(PathBuf::from);
+  let mut planned: HashSet<String> = HashSet::new();
+  let mut out = Vec::with_capacity(units.len());
+  for u in units {
+    let is_taken = |name: &str| planned.contains(name) || dir_path.as_ref().is_some_and(|d| d.join(name).exists());
+    let plain = unique_file_name(&u.id, &ext, |_| false)?;
+    let filename = unique_file_name(&u.id, &ext, is_taken)?;
+    planned.insert(filename.clone());
+    out.push(PlannedFilename { id: u.id, collision: filename != plain, filename });
+  }
+  Ok(out)
+}
+
+// ⬇ put this helper anywhere above `run()` (e.g., with other helpers)
+fn sanitize_for_filename(input: &str) -> String {
+  // Keep alnum, dot, dash, underscore. Everything else -> underscore.
+  let mut out: String = input
+    .chars()
+    .map(|c| {
+      if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect();
+  while out.contains("__") {
+    out = out.replace("__", "_");
+  }
+  out.trim_matches('_').to_string()
+}
+
+#[tauri::command]
+fn save_chunk_file(dir: String, base: String, ext: Option<String>, contents: String) -> Result<String, CommandError> {
+  let dir_path = PathBuf::from(&dir);
+  // Ensure directory exists
+  create_dir_all(&dir_path).map_err(|e| CommandError::Io(format!("mkdir failed: {}", e)))?;
+
+  let final_path = write_unique_file(&dir_path, &base, ext.as_deref().unwrap_or("md"), &contents)?;
+  Ok(final_path.to_string_lossy().to_string())
+}
+
+fn write_unique_file(dir_path: &Path, base: &str, ext: &str, contents: &str) -> Result<PathBuf, CommandError> {
+  let name = unique_file_name(base, ext, |name| dir_path.join(name).exists())?;
+  let final_path = dir_path.join(name);
+  fs::write(&final_path, contents).map_err(|e| CommandError::Io(format!("write failed: {}", e)))?;
+  Ok(final_path)
+}
+
+#[tauri::command]
+fn split_file_to_dir(
+  path: String,
+  dir: String,
+  chunk_bytes: usize,
+  base: Option<String>,
+  ext: Option<String>,
+) -> Result<Vec<String>, CommandError> {
+  if chunk_bytes == 0 {
+    return Err(CommandError::Config("chunkBytes must be greater than 0".into()));
+  }
+  let data = fs::read(&path).map_err(|e| CommandError::io_at(&path, e))?;
+  let text = decode_text(&data);
+  let base = base.unwrap_or_else(|| {
+    Path::new(&path).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "chunk".into())
+  });
+  let ext = ext.unwrap_or_else(|| "md".to_string());
+
+  let dir_path = PathBuf::from(&dir);
+  create_dir_all(&dir_path).map_err(|e| CommandError::io_at(&dir, e))?;
+  pack_paragraphs(&text, chunk_bytes).iter().enumerate()
+    .map(|(i, chunk)| {
+      let written = write_unique_file(&dir_path, &format!("{}-{:03}", base, i + 1), &ext, chunk)?;
+      Ok(written.to_string_lossy().to_string())
+    })
+    .collect()
+}
+
+/* ---------- Resumable batch save (manifest.json) ---------- */
+
+const MANIFEST_FILENAME: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+  file: String,   // name within the output dir
+  hash: String,   // sha256 of the body
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SaveManifestResult {
+  written: usize,
+  skipped: usize,
+}
+
+// Units already in the manifest with the same body hash are skipped; a changed
+// body overwrites the file it was saved to before
+#[tauri::command]
+fn save_units_with_manifest(dir: String, units: Vec<PromptUnit>, ext: Option<String>) -> Result<SaveManifestResult, CommandError> {
+  let dir_path = PathBuf::from(&dir);
+  create_dir_all(&dir_path).map_err(|e| CommandError::io_at(&dir, e))?;
+  let ext = ext.unwrap_or_else(|| "md".to_string());
+
+  let manifest_path = dir_path.join(MANIFEST_FILENAME);
+  let mut manifest: BTreeMap<String, ManifestEntry> = match fs::read_to_string(&manifest_path) {
+    Ok(text) => serde_json::from_str(&text)
+      .map_err(|e| CommandError::Parse(format!("{}: {}", manifest_path.display(), e)))?,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+    Err(e) => return Err(CommandError::io_at(manifest_path.display(), e)),
+  };
+
+  let mut result = SaveManifestResult { written: 0, skipped: 0 };
+  for unit in &units {
+    let hash = sha256_hex(unit.body.as_bytes());
+    let existing = manifest.get(&unit.id).filter(|e| dir_path.join(&e.file).is_file());
+    match existing {
+      Some(entry) if entry.hash == hash => {
+        result.skipped += 1;
+        continue;
+      }
+      Some(entry) => {
+        let path = dir_path.join(&entry.file);
+        fs::write(&path, &unit.body).map_err(|e| CommandError::io_at(path.display(), e))?;
+        let file = entry.file.clone();
+        manifest.insert(unit.id.clone(), ManifestEntry { file, hash });
+      }
+      None => {
+        let path = write_unique_file(&dir_path, &unit.id, &ext, &unit.body)?;
+        let file = path.file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+        manifest.insert(unit.id.clone(), ManifestEntry { file, hash });
+      }
+    }
+    result.written += 1;
+  }
+
+  let text = serde_json::to_string_pretty(&manifest)
+    .map_err(|e| CommandError::Parse(e.to_string()))?;
+  fs::write(&manifest_path, text).map_err(|e| CommandError::io_at(manifest_path.display(), e))?;
+  Ok(result)
+}
+
+// ASCII filter for downloaded bytes (keeps \t \n \r and printable ASCII)
+fn ascii_only_from_bytes(buf: &[u8]) -> String {
+  let mut out = String::with_capacity(buf.len());
+  for &b in buf.iter() {
+    match b {
+      9 | 10 | 13 => out.push(b as char),       // \t \n \r
+      32..=126 => out.push(b as char),          // printable ASCII
+      _ => {}
+    }
+  }
+  out
+}
+
+// Caller-supplied cookies for the first URL. reqwest's jar then stores Set-Cookie
+// (Domain, Path and expiry honored) and sends it on later requests and redirects
+fn seeded_cookie_jar(url: &str, cookies: Option<HashMap<String, String>>) -> Result<Arc<reqwest::cookie::Jar>, CommandError> {
+  let jar = reqwest::cookie::Jar::default();
+  if let Some(cookies) = cookies.filter(|c| !c.is_empty()) {
+    let url = reqwest::Url::parse(url).map_err(|e| CommandError::Config(format!("Invalid URL {}: {}", url, e)))?;
+    for (name, value) in cookies {
+      jar.add_cookie_str(&format!("{}={}; Path=/", name, value), &url);
+    }
+  }
+  Ok(Arc::new(jar))
+}
+
+// GET a page with browser-like Accept headers and return its text
+async fn get_page_text(client: &reqwest::Client, url: &str, max_bytes: usize) -> Result<String, CommandError> {
+  let resp = client
+    .get(url)
+    .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+    .header(reqwest::header::ACCEPT_LANGUAGE, "en-US,en;q=0.9")
+    .send()
+    .await
+    .map_err(|e| CommandError::Network(format!("GET {} failed: {}", url, e)))?;
+  tracing::debug!(url = %url, status = %resp.status(), "GET");
+
+  if !resp.status().is_success() {
+    return Err(CommandError::Network(format!("GET {} returned {}", url, resp.status())));
+  }
+
+  let bytes = read_body_capped(resp, max_bytes).await?;
+  // A gzip magic header here means the body was compressed without a usable Content-Encoding
+  if bytes.starts_with(&[0x1f, 0x8b]) {
+    return Err(CommandError::Parse(format!("GET {} returned a compressed body that could not be decoded to text", url)));
+  }
+  Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// An explicit proxy handles every scheme; without one reqwest keeps using
+// HTTP_PROXY / HTTPS_PROXY / NO_PROXY from the environment
+fn with_proxy(builder: reqwest::ClientBuilder, proxy: Option<&str>) -> Result<reqwest::ClientBuilder, CommandError> {
+  match proxy.map(str::trim).filter(|p| !p.is_empty()) {
+    Some(url) => {
+      let proxy = reqwest::Proxy::all(url)
+        .map_err(|e| CommandError::Config(format!("Invalid proxy URL {}: {}", url, e)))?;
+      Ok(builder.proxy(proxy))
+    }
+    None => Ok(builder),
+  }
+}
+
+// Client for fetching pages: browser user agent unless overridden, up to 10 redirects,
+// and cookies kept in `jar` for the life of the command
+fn browser_client(user_agent: Option<&str>, proxy: Option<&str>, jar: Arc<reqwest::cookie::Jar>) -> Result<reqwest::Client, CommandError> {
+  Ok(with_proxy(reqwest::Client::builder(), proxy)?
+    .gzip(true)
+    .deflate(true)
+    .brotli(true)
+    .user_agent(user_agent.unwrap_or(BROWSER_USER_AGENT))
+    .redirect(reqwest::redirect::Policy::limited(10))
+    .cookie_provider(jar)
+    .build()?)
+}
+
+// Fallback URL to try when the first page lacks `success_marker`
+// (e.g. eCFR: from "/on/", to "/current/", marker "flush-paragraph-2")
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UrlRewrite {
+  from: String,
+  to: String,
+  success_marker: String,
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(endpoint = %endpoint, url = %url), err)]
+async fn fetch_api_table_from_url(
+  endpoint: String,
+  url: String,
+  user_agent: Option<String>,
+  retry_url_rewrite: Option<UrlRewrite>,
+  max_response_bytes: Option<usize>,
+  proxy: Option<String>,
+  cookies: Option<HashMap<String, String>>,
+) -> Result<ApiTable, CommandError> {
+  let max_response = max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+  // 1) Download the source URL (try to mimic a real browser unless told otherwise)
+  let client = browser_client(user_agent.as_deref(), proxy.as_deref(), seeded_cookie_jar(&url, cookies)?)?;
+
+  let mut html_text = get_page_text(&client, &url, max_response).await?;
+
+  // 1b) Optional fallback: if the expected marker is missing, try the rewritten URL
+  if let Some(rw) = &retry_url_rewrite {
+    if !rw.from.is_empty() && url.contains(&rw.from) && !html_text.contains(&rw.success_marker) {
+      let retry_url = url.replace(&rw.from, &rw.to);
+      // Only replace if the fallback actually looks better
+      if let Ok(html2) = get_page_text(&client, &retry_url, max_response).await {
+        if html2.contains(&rw.success_marker) {
+          html_text = html2;
+        }
+      }
+    }
+  }
+
+  // 2) Post the ASCII/UTF-8 text to your extraction API as { data: ... }
+  post_page_for_table(&client, &endpoint, html_text, max_response).await
+}
+
+// Posts page text to the extraction API as { data: ... } and normalizes the
+// returned array of objects into a table
+async fn post_page_for_table(
+  client: &reqwest::Client,
+  endpoint: &str,
+  html_text: String,
+  max_response: usize,
+) -> Result<ApiTable, CommandError> {
+  let req = client
+    .post(endpoint)
+    .header(reqwest::header::CONTENT_TYPE, "application/json")
+    .json(&serde_json::json!({ "data": html_text }));  // ⬅️ your FastAPI expects "data"
+  let resp = send_respecting_retry_after(req)
+    .await
+    .map_err(|e| CommandError::Network(format!("POST {} failed: {}", endpoint, e)))?;
+  tracing::debug!(url = %endpoint, status = %resp.status(), "POST");
+
+  if !resp.status().is_success() {
+    return Err(CommandError::Network(format!("Extraction API error {} from {}", resp.status(), endpoint)));
+  }
+
+  let v: Value = read_json_response(resp, max_response).await?;
+  let objs = find_array_of_objects(&v)
+    .ok_or_else(|| CommandError::Parse("No array of objects in extraction response".into()))?;
+
+  // Normalize to columns + rows table
+  let mut cols: BTreeSet<String> = BTreeSet::new();
+  for o in &objs { for k in o.keys() { cols.insert(k.clone()); } }
+  let columns: Vec<String> = cols.into_iter().collect();
+
+  let mut rows: Vec<HashMap<String, String>> = Vec::new();
+  for o in objs {
+    let mut r = HashMap::new();
+    for c in &columns {
+      let s = o.get(c).map(json_to_string).unwrap_or_default();
+      r.insert(c.clone(), s);
+    }
+    rows.push(r);
+  }
+
+  Ok(ApiTable { columns, rows, raw: None })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UrlError {
+  url: String,
+  message: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MultiUrlTable {
+  table: ApiTable,
+  errors: Vec<UrlError>,
+}
+
+// fetch_api_table_from_url for many pages: up to `max_concurrency` (default 4) in
+// flight, rows merged in URL order with a `source_url` column, and a failed URL
+// reported in `errors` instead of failing the call
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(endpoint = %endpoint, urls = urls.len()), err)]
+async fn fetch_api_table_from_urls(
+  endpoint: String,
+  urls: Vec<String>,
+  user_agent: Option<String>,
+  max_response_bytes: Option<usize>,
+  proxy: Option<String>,
+  max_concurrency: Option<usize>,
+) -> Result<MultiUrlTable, CommandError> {
+  use futures_util::stream::{self, StreamExt};
+
+  let max_response = max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+  let client = browser_client(user_agent.as_deref(), proxy.as_deref(), Default::default())?;
+  let (client, endpoint) = (&client, endpoint.as_str());
+
+  let results: Vec<(String, Result<ApiTable, CommandError>)> = stream::iter(urls)
+    .map(|url| async move {
+      let table = async {
+        let html_text = get_page_text(client, &url, max_response).await?;
+        post_page_for_table(client, endpoint, html_text, max_response).await
+      }.await;
+      (url, table)
+    })
+    .buffered(max_concurrency.unwrap_or(4).max(1))
+    .collect()
+    .await;
+
+  let mut tables = Vec::new();
+  let mut errors = Vec::new();
+  for (url, result) in results {
+    match result {
+      Ok(mut table) => {
+        if !table.columns.iter().any(|c| c == "source_url") {
+          table.columns.insert(0, "source_url".to_string());
+        }
+        for r in table.rows.iter_mut() {
+          r.insert("source_url".to_string(), url.clone());
+        }
+        tables.push(table);
+      }
+      Err(e) => errors.push(UrlError { url, message: e.to_string() }),
+    }
+  }
+  Ok(MultiUrlTable { table: merge_tables(tables)?, errors })
+}
+
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(url = %url), err)]
+async fn extract_html_blocks_from_url(
+  url: String,
+  config: HtmlConfig,
+  user_agent: Option<String>,
+  max_response_bytes: Option<usize>,
+  absolute_urls: Option<bool>,
+  proxy: Option<String>,
+  follow_iframes: Option<bool>,
+) -> Result<Vec<PromptUnit>, CommandError> {
+  let client = browser_client(user_agent.as_deref(), proxy.as_deref(), Default::default())?;
+  let max_response = max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+  let base = reqwest::Url::parse(&url).map_err(|e| CommandError::Config(format!("Invalid URL {}: {}", url, e)))?;
+  let absolute = absolute_urls.unwrap_or(false);
+  let html_text = get_page_text(&client, &url, max_response).await?;
+  let mut doc = Html::parse_document(&html_text);
+  if absolute {
+    absolutize_links(&mut doc, &base);
+  }
+  if !follow_iframes.unwrap_or(false) {
+    return html_units_from_doc(&doc, &config);
+  }
+
+  // Same-origin frames only, one level deep; their bodies are appended to the
+  // page body so the selectors see one combined document
+  let iframe_sel = Selector::parse("iframe[src]").expect("static selector");
+  let frame_urls: Vec<reqwest::Url> = doc.select(&iframe_sel)
+    .filter_map(|f| f.value().attr("src"))
+    .filter_map(|src| base.join(src.trim()).ok())
+    .filter(|u| u.origin() == base.origin())
+    .take(MAX_IFRAMES)
+    .collect();
+
+  let mut combined = body_inner_html(&doc);
+  for frame_url in frame_urls {
+    match get_page_text(&client, frame_url.as_str(), max_response).await {
+      Ok(text) => {
+        let mut frame = Html::parse_document(&text);
+        if absolute {
+          absolutize_links(&mut frame, &frame_url);
+        }
+        combined.push_str(&body_inner_html(&frame));
+      }
+      Err(e) => tracing::debug!(url = %frame_url, error = %e, "iframe skipped"),
+    }
+  }
+  let combined = Html::parse_document(&format!("<html><body>{}</body></html>", combined));
+  html_units_from_doc(&combined, &config)
+}
+
+// Upper bound on iframes fetched per page by follow_iframes
+const MAX_IFRAMES: usize = 5;
+
+fn body_inner_html(doc: &Html) -> String {
+  let body_sel = Selector::parse("body").expect("static selector");
+  doc.select(&body_sel).next().map(|b| b.inner_html()).unwrap_or_default()
+}
+
+// Rewrite relative href/src attributes against the page URL
+fn absolutize_links(doc: &mut Html, base: &reqwest::Url) {
+  for node in doc.tree.values_mut() {
+    let scraper::Node::Element(el) = node else { continue; };
+    for (name, value) in el.attrs.iter_mut() {
+      if &*name.local != "href" && &*name.local != "src" { continue; }
+      if let Ok(abs) = base.join(value.trim()) {
+        *value = abs.as_str().into();
+      }
+    }
+  }
+}
+
+/* ---------- Endpoint health check ---------- */
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointStatus {
+  reachable: bool,
+  status: Option<u16>,
+  latency_ms: u64,
+}
+
+// Any HTTP response counts as reachable (a 404 still means the server is up).
+// HEAD first; servers that reject it (405/501) or fail it are retried with GET.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(endpoint = %endpoint))]
+async fn ping_endpoint(endpoint: String, timeout_ms: Option<u64>, proxy: Option<String>) -> Result<EndpointStatus, CommandError> {
+  let url = reqwest::Url::parse(endpoint.trim())
+    .map_err(|e| CommandError::Config(format!("Invalid endpoint URL {}: {}", endpoint, e)))?;
+  let client = with_proxy(reqwest::Client::builder(), proxy.as_deref())?
+    .timeout(Duration::from_millis(timeout_ms.unwrap_or(5000)))
+    .user_agent(BROWSER_USER_AGENT)
+    .build()?;
+
+  let started = std::time::Instant::now();
+  let head = client.head(url.clone()).send().await;
+  let resp = match head {
+    Ok(r) if r.status() != reqwest::StatusCode::METHOD_NOT_ALLOWED && r.status() != reqwest::StatusCode::NOT_IMPLEMENTED => Ok(r),
+    _ => client.get(url).send().await,
+  };
+  let latency_ms = started.elapsed().as_millis() as u64;
+
+  match resp {
+    Ok(r) => {
+      tracing::debug!(status = %r.status(), latency_ms, "ping");
+      Ok(EndpointStatus { reachable: true, status: Some(r.status().as_u16()), latency_ms })
+    }
+    Err(e) => {
+      tracing::debug!(error = %e, latency_ms, "ping failed");
+      Ok(EndpointStatus { reachable: false, status: None, latency_ms })
+    }
+  }
+}
+
+/* ---------- Extension-based dispatch ---------- */
+
+fn sub_config<T: serde::de::DeserializeOwned>(config: Value, kind: &str) -> Result<T, CommandError> {
+  serde_json::from_value(config).map_err(|e| CommandError::Config(format!("Invalid {} config: {}", kind, e)))
+}
+
+// Picks the extractor from the file extension. calamine cannot read CSV, so .csv
+// falls through with other text: regex blocks when `delimiter` is set, else paragraphs
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(path = %path), err)]
+fn extract_auto(path: String, config: Value) -> Result<Vec<PromptUnit>, CommandError> {
+  let ext = Path::new(&path).extension()
+    .map(|e| e.to_string_lossy().to_ascii_lowercase())
+    .unwrap_or_default();
+  match ext.as_str() {
+    "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => extract_excel_units(path, sub_config(config, "Excel")?).map(ExcelUnitsResult::into_units),
+    "html" | "htm" => extract_html_blocks(path, sub_config(config, "HTML")?),
+    "json" | "jsonl" | "ndjson" => extract_json_units(path, sub_config(config, "JSON")?),
+    _ if config.get("delimiter").is_some() => extract_regex_blocks(path, sub_config(config, "regex")?),
+    _ => {
+      let min_len = config.get("minLen").and_then(Value::as_u64).map(|n| n as usize);
+      extract_paragraphs(path, min_len)
+    }
+  }
+}
+
+/* ---------- Token / cost estimate ---------- */
+
+// No tokenizer ships with the app, so this is the usual ~4 chars per token rule
+// of thumb for English text and code; treat results as a budget, not a bill
+fn estimate_tokens(text: &str) -> usize {
+  text.chars().count().div_ceil(4)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TextCost {
+  tokens: usize,
+  cost: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CostEstimate {
+  model: Option<String>,
+  texts: Vec<TextCost>,
+  total_tokens: usize,
+  total_cost: f64,
+}
+
+#[tauri::command]
+fn estimate_cost(texts: Vec<String>, input_price_per_1k: f64, model: Option<String>) -> Result<CostEstimate, CommandError> {
+  if !input_price_per_1k.is_finite() || input_price_per_1k < 0.0 {
+    return Err(CommandError::Config(format!("Invalid price per 1k tokens: {}", input_price_per_1k)));
+  }
+  let per_text: Vec<TextCost> = texts.iter().map(|t| {
+    let tokens = estimate_tokens(t);
+    TextCost { tokens, cost: tokens as f64 / 1000.0 * input_price_per_1k }
+  }).collect();
+  let total_tokens = per_text.iter().map(|t| t.tokens).sum::<usize>();
+  Ok(CostEstimate {
+    model,
+    texts: per_text,
+    total_tokens,
+    total_cost: total_tokens as f64 / 1000.0 * input_price_per_1k,
+  })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UnitTokens {
+  id: String,
+  tokens: usize,
+  over_limit: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OversizeReport {
+  model: Option<String>,
+  max_tokens: usize,
+  units: Vec<UnitTokens>,
+  over_limit_count: usize,
+}
+
+// Same estimate as estimate_cost, per unit body, against a context budget
+#[tauri::command]
+fn flag_oversized_units(units: Vec<PromptUnit>, max_tokens: usize, model: Option<String>) -> Result<OversizeReport, CommandError> {
+  let units: Vec<UnitTokens> = units.into_iter().map(|u| {
+    let tokens = estimate_tokens(&u.body);
+    UnitTokens { id: u.id, tokens, over_limit: tokens > max_tokens }
+  }).collect();
+  let over_limit_count = units.iter().filter(|u| u.over_limit).count();
+  Ok(OversizeReport { model, max_tokens, units, over_limit_count })
+}
+
+// Archives a page as fetched (browser-like GET, text decoded) and returns the written path
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(url = %url), err)]
+async fn download_url_to_file(
+  url: String,
+  dir: String,
+  base: Option<String>,
+  ext: Option<String>,
+  user_agent: Option<String>,
+  proxy: Option<String>,
+  max_response_bytes: Option<usize>,
+) -> Result<String, CommandError> {
+  let client = browser_client(user_agent.as_deref(), proxy.as_deref(), Default::default())?;
+  let max_response = max_response_bytes.unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+  let text = get_page_text(&client, &url, max_response).await?;
+
+  // Default name: the last path segment (minus its extension), else the host
+  let base = base.filter(|b| !b.trim().is_empty()).unwrap_or_else(|| {
+    reqwest::Url::parse(&url).ok()
+      .and_then(|u| {
+        let segment = u.path_segments()
+          .and_then(|mut s| s.next_back())
+          .and_then(|s| Path::new(s).file_stem().map(|f| f.to_string_lossy().to_string()))
+          .filter(|s| !s.is_empty());
+        segment.or_else(|| u.host_str().map(str::to_string))
+      })
+      .unwrap_or_else(|| "page".into())
+  });
+  let dir_path = PathBuf::from(&dir);
+  create_dir_all(&dir_path).map_err(|e| CommandError::io_at(&dir, e))?;
+  let path = write_unique_file(&dir_path, &base, ext.as_deref().unwrap_or("html"), &text)?;
+  Ok(path.to_string_lossy().to_string())
+}
+
+/* ---------- Merge units into one prompt ---------- */
+
+// Simple `{id}` / `{body}` placeholder substitution
+fn render_unit_template(template: &str, unit: &PromptUnit) -> String {
+  template.replace("{id}", &unit.id).replace("{body}", &unit.body)
+}
+
+#[tauri::command]
+fn merge_units(units: Vec<PromptUnit>, separator: Option<String>, template: Option<String>) -> Result<String, CommandError> {
+  let sep = separator.unwrap_or_else(|| "\n\n".to_string());
+  let parts: Vec<String> = units.iter().map(|u| match &template {
+    Some(t) => render_unit_template(t, u),
+    None => u.body.clone(),
+  }).collect();
+  Ok(parts.join(&sep))
+}
+
+// Keeps the first unit for each body hash. With `normalize`, bodies are compared with
+// runs of whitespace collapsed and ends trimmed ("foo  bar" == "foo bar"); the kept
+// unit's body is returned unchanged either way.
+#[tauri::command]
+fn dedupe_units(units: Vec<PromptUnit>, normalize: Option<bool>) -> Result<Vec<PromptUnit>, CommandError> {
+  let normalize = normalize.unwrap_or(false);
+  let mut seen: HashSet<String> = HashSet::new();
+  Ok(units.into_iter().filter(|u| {
+    let hash = if normalize {
+      sha256_hex(u.body.split_whitespace().collect::<Vec<_>>().join(" ").as_bytes())
+    } else {
+      sha256_hex(u.body.as_bytes())
+    };
+    seen.insert(hash)
+  }).collect())
+}
+
+const DEFAULT_MARKDOWN_UNIT: &str = "## {id}\n\n{body}\n\n";
+
+// Writes every unit into one .md file (named like save_chunk_file) and returns its path
+#[tauri::command]
+fn export_markdown(
+  dir: String,
+  base: String,
+  units: Vec<PromptUnit>,
+  heading_template: Option<String>,
+  include_meta: Option<bool>,
+) -> Result<String, CommandError> {
+  let template = heading_template.unwrap_or_else(|| DEFAULT_MARKDOWN_UNIT.to_string());
+  let mut doc = String::new();
+  for u in &units {
+    doc.push_str(&render_unit_template(&template, u));
+    if !include_meta.unwrap_or(false) { continue; }
+    if let Some(Value::Object(meta)) = &u.meta {
+      for (k, v) in meta {
+        doc.push_str(&format!("- {}: {}\n", k, json_to_string(v)));
+      }
+      if !meta.is_empty() { doc.push('\n'); }
+    }
+  }
+
+  let dir_path = PathBuf::from(&dir);
+  create_dir_all(&dir_path).map_err(|e| CommandError::io_at(&dir, e))?;
+  let path = write_unique_file(&dir_path, &base, "md", &format!("{}\n", doc.trim_end()))?;
+  Ok(path.to_string_lossy().to_string())
+}
+
+// One `{"messages": [...]}` line per unit for chat fine-tuning: optional system
+// message, the user message from `prompt_template` ({id} / {body}), and an
+// assistant message from meta[assistant_meta_key] when that key is present
+#[tauri::command]
+fn export_chat_jsonl(
+  dir: String,
+  base: String,
+  units: Vec<PromptUnit>,
+  system_prompt: Option<String>,
+  prompt_template: String,
+  assistant_meta_key: Option<String>,
+) -> Result<String, CommandError> {
+  let system_prompt = system_prompt.filter(|s| !s.trim().is_empty());
+  let mut out = String::new();
+  for u in &units {
+    let mut messages: Vec<Value> = Vec::new();
+    if let Some(sys) = &system_prompt {
+      messages.push(serde_json::json!({ "role": "system", "content": sys }));
+    }
+    messages.push(serde_json::json!({ "role": "user", "content": render_unit_template(&prompt_template, u) }));
+    let answer = assistant_meta_key.as_deref()
+      .and_then(|k| u.meta.as_ref().and_then(|m| m.get(k)))
+      .map(json_to_string);
+    if let Some(answer) = answer {
+      messages.push(serde_json::json!({ "role": "assistant", "content": answer }));
+    }
+    out.push_str(&serde_json::json!({ "messages": messages }).to_string());
+    out.push('\n');
+  }
+
+  let dir_path = PathBuf::from(&dir);
+  create_dir_all(&dir_path).map_err(|e| CommandError::io_at(&dir, e))?;
+  let path = write_unique_file(&dir_path, &base, "jsonl", &out)?;
+  Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Writes `contents` to dir/name (creating parent dirs) and returns the path as a String
+  fn write_file(dir: &Path, name: &str, contents: impl AsRef<[u8]>) -> String {
+    let p = dir.join(name);
+    if let Some(parent) = p.parent() {
+      fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(&p, contents).unwrap();
+    p.to_string_lossy().to_string()
+  }
+
+  fn read_one(path: &str, selections: Option<Vec<LineSelection>>) -> FileValue {
+    let mut out = read_ascii_files(vec![path.to_string()], None, selections, None, None, None, None).unwrap();
+    assert_eq!(out.len(), 1);
+    out.remove(0)
+  }
+
+  /* ---------- read_ascii_files line selections ---------- */
+
+  #[test]
+  fn selection_returns_inclusive_line_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "six.txt", "one\ntwo\nthree\nfour\nfive\nsix\n");
+    let sel = LineSelection { path: path.clone(), start_line: 2, end_line: 4 };
+    assert_eq!(read_one(&path, Some(vec![sel])).value, "two\nthree\nfour");
+  }
+
+  #[test]
+  fn selection_clamps_out_of_range_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "six.txt", "one\ntwo\nthree\nfour\nfive\nsix\n");
+    let sel = LineSelection { path: path.clone(), start_line: 0, end_line: 99 };
+    assert_eq!(read_one(&path, Some(vec![sel])).value, "one\ntwo\nthree\nfour\nfive\nsix");
+    let past_end = LineSelection { path: path.clone(), start_line: 10, end_line: 12 };
+    assert_eq!(read_one(&path, Some(vec![past_end])).value, "");
+  }
+
+  #[test]
+  fn selection_only_applies_to_its_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "a.txt", "a1\na2\na3\n");
+    let sel = LineSelection { path: "elsewhere.txt".into(), start_line: 2, end_line: 2 };
+    assert_eq!(read_one(&path, Some(vec![sel])).value, "a1\na2\na3\n");
+  }
+
+  /* ---------- Language detection ---------- */
+
+  #[test]
+  fn language_from_extension() {
+    assert_eq!(language_for_path(Path::new("src/main.rs")), "rust");
+    assert_eq!(language_for_path(Path::new("tool.py")), "python");
+    assert_eq!(language_for_path(Path::new("app.ts")), "typescript");
+    assert_eq!(language_for_path(Path::new("README.MD")), "markdown");
+    assert_eq!(language_for_path(Path::new("data.yml")), "yaml");
+  }
+
+  #[test]
+  fn language_falls_back_to_text() {
+    assert_eq!(language_for_path(Path::new("archive.xyz")), "text");
+    assert_eq!(language_for_path(Path::new("Makefile")), "text");
+  }
+
+  #[test]
+  fn file_value_carries_language() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "lib.rs", "fn main() {}\n");
+    assert_eq!(read_one(&path, None).language, "rust");
+  }
+
+  /* ---------- merge_units ---------- */
+
+  fn unit(id: &str, body: &str) -> PromptUnit {
+    PromptUnit { id: id.into(), body: body.into(), meta: None }
+  }
+
+  #[test]
+  fn merge_units_joins_bodies_with_default_separator() {
+    let merged = merge_units(vec![unit("a", "first"), unit("b", "second")], None, None).unwrap();
+    assert_eq!(merged, "first\n\nsecond");
+  }
+
+  #[test]
+  fn merge_units_renders_template_per_unit() {
+    let units = vec![unit("a", "first"), unit("b", "second")];
+    let merged = merge_units(units, Some("\n---\n".into()), Some("### {id}\n{body}".into())).unwrap();
+    assert_eq!(merged, "### a\nfirst\n---\n### b\nsecond");
+  }
+
+  /* ---------- Excel fixtures ---------- */
+
+  fn col_letters(mut col: usize) -> String {
+    let mut out = Vec::new();
+    loop {
+      out.insert(0, (b'A' + (col % 26) as u8) as char);
+      if col < 26 { break; }
+      col = col / 26 - 1;
+    }
+    out.into_iter().collect()
+  }
+
+  // Minimal .xlsx writer: one worksheet per (name, rows) with inline string cells.
+  // "" leaves a cell empty and "=EXPR->V" is a formula whose cached value is V (an
+  // error value such as #DIV/0! when V starts with #).
+  // `names` become workbook defined names, e.g. ("PriceTable", "Items!$A$1:$B$3").
+  fn write_xlsx(path: &Path, sheets: &[(&str, Vec<Vec<&str>>)], names: &[(&str, &str)]) {
+    use std::io::Write;
+    let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+    let opts = zip::write::FileOptions::default();
+    let mut put = |name: &str, body: String| {
+      zip.start_file(name, opts).unwrap();
+      zip.write_all(body.as_bytes()).unwrap();
+    };
+
+    let overrides: String = (1..=sheets.len()).map(|i| format!(
+      r#"<Override PartName="/xl/worksheets/sheet{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#, i
+    )).collect();
+    put("[Content_Types].xml", format!(
+      r#"<?xml version="1.0"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>{}</Types>"#,
+      overrides
+    ));
+    put("_rels/.rels", r#"<?xml version="1.0"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#.into());
+
+    let sheet_list: String = sheets.iter().enumerate()
+      .map(|(i, (name, _))| format!(r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#, name, i + 1, i + 1))
+      .collect();
+    let defined: String = names.iter()
+      .map(|(name, formula)| format!(r#"<definedName name="{}">{}</definedName>"#, name, formula))
+      .collect();
+    put("xl/workbook.xml", format!(
+      r#"<?xml version="1.0"?><workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>{}</sheets><definedNames>{}</definedNames></workbook>"#,
+      sheet_list, defined
+    ));
+    let rels: String = (1..=sheets.len()).map(|i| format!(
+      r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{}.xml"/>"#, i, i
+    )).collect();
+    put("xl/_rels/workbook.xml.rels", format!(
+      r#"<?xml version="1.0"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{}</Relationships>"#, rels
+    ));
+
+    for (i, (_, rows)) in sheets.iter().enumerate() {
+      let mut data = String::new();
+      for (r, row) in rows.iter().enumerate() {
+        data.push_str(&format!(r#"<row r="{}">"#, r + 1));
+        for (c, v) in row.iter().enumerate() {
+          let cell_ref = format!("{}{}", col_letters(c), r + 1);
+          if v.is_empty() { continue; }
+          match v.strip_prefix('=').and_then(|f| f.split_once("->")) {
+            Some((formula, cached)) => {
+              let kind = if cached.starts_with('#') { "e" } else { "str" };
+              data.push_str(&format!(r#"<c r="{}" t="{}"><f>{}</f><v>{}</v></c>"#, cell_ref, kind, formula, cached))
+            }
+            None => data.push_str(&format!(r#"<c r="{}" t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#, cell_ref, v)),
+          }
+        }
+        data.push_str("</row>");
+      }
+      put(&format!("xl/worksheets/sheet{}.xml", i + 1), format!(
+        r#"<?xml version="1.0"?><worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>{}</sheetData></worksheet>"#, data
+      ));
+    }
+    zip.finish().unwrap();
+  }
+
+  fn excel_config(sheet: &str, id_column: &str, description_columns: &[&str]) -> ExcelConfig {
+    serde_json::from_value(serde_json::json!({
+      "sheet": sheet,
+      "idColumn": id_column,
+      "descriptionColumns": description_columns,
+    })).unwrap()
+  }
+
+  fn excel_units(path: &Path, config: ExcelConfig) -> Vec<PromptUnit> {
+    extract_excel_units(path.to_string_lossy().to_string(), config).unwrap().into_units()
+  }
+
+  fn ids(units: &[PromptUnit]) -> Vec<&str> {
+    units.iter().map(|u| u.id.as_str()).collect()
+  }
+
+  /* ---------- Excel row filters ---------- */
+
+  #[test]
+  fn excel_filter_keeps_matching_rows_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("status.xlsx");
+    write_xlsx(&path, &[("Items", vec![
+      vec!["ID", "Status", "Text"],
+      vec!["a", "approved", "alpha"],
+      vec!["b", "draft", "beta"],
+      vec!["c", " approved ", "gamma"],
+    ])], &[]);
+    let mut config = excel_config("Items", "ID", &["Text"]);
+    config.filters = Some(vec![ExcelFilter { column: "status".into(), equals: "approved".into() }]);
+    assert_eq!(ids(&excel_units(&path, config)), ["a", "c"]);
+  }
+
+  #[test]
+  fn excel_filter_on_unknown_column_is_a_config_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("status.xlsx");
+    write_xlsx(&path, &[("Items", vec![vec!["ID", "Text"], vec!["a", "alpha"]])], &[]);
+    let mut config = excel_config("Items", "ID", &["Text"]);
+    config.filters = Some(vec![ExcelFilter { column: "Status".into(), equals: "approved".into() }]);
+    let err = extract_excel_units(path.to_string_lossy().to_string(), config).err().unwrap();
+    assert!(matches!(err, CommandError::Config(_)), "{}", err);
+  }
+
+  /* ---------- cell_to_string ---------- */
+
+  #[test]
+  fn cell_to_string_passes_iso_datetimes_through() {
+    let c = DataType::DateTimeIso(" 2024-03-01T10:15:00 ".into());
+    assert_eq!(cell_to_string(&c).as_deref(), Some("2024-03-01T10:15:00"));
+  }
+
+  #[test]
+  fn cell_to_string_formats_iso_durations_as_hms() {
+    assert_eq!(cell_to_string(&DataType::DurationIso("PT10H30M5S".into())).as_deref(), Some("10:30:05"));
+    assert_eq!(cell_to_string(&DataType::DurationIso("P1DT2H".into())).as_deref(), Some("26:00:00"));
+    assert_eq!(cell_to_string(&DataType::DurationIso("PT1.5S".into())).as_deref(), Some("00:00:01"));
+  }
+
+  #[test]
+  fn cell_to_string_keeps_ambiguous_durations_verbatim() {
+    // Months have no fixed length, so the ISO text is kept
+    assert_eq!(cell_to_string(&DataType::DurationIso("P1M".into())).as_deref(), Some("P1M"));
+  }
+
+  #[test]
+  fn cell_to_string_drops_trailing_zero_on_whole_floats() {
+    assert_eq!(cell_to_string(&DataType::Float(3.0)).as_deref(), Some("3"));
+    assert_eq!(cell_to_string(&DataType::Float(2.5)).as_deref(), Some("2.5"));
+    assert_eq!(cell_to_string(&DataType::Empty), None);
+  }
+
+  /* ---------- preview_excel_units ---------- */
+
+  fn mixed_rows_workbook(dir: &Path) -> PathBuf {
+    let path = dir.join("mixed.xlsx");
+    write_xlsx(&path, &[("Rows", vec![
+      vec!["ID", "Status", "Text"],
+      vec!["a", "ok", "alpha"],
+      vec!["", "ok", "no id"],
+      vec!["c", "ok", ""],
+      vec!["d", "hold", "filtered"],
+      vec!["e", "ok", "echo"],
+    ])], &[]);
+    path
+  }
+
+  #[test]
+  fn preview_counts_each_skip_reason() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = mixed_rows_workbook(dir.path());
+    let mut config = excel_config("Rows", "ID", &["Text"]);
+    config.filters = Some(vec![ExcelFilter { column: "Status".into(), equals: "ok".into() }]);
+    let p = preview_excel_units(path.to_string_lossy().to_string(), config).unwrap();
+    assert_eq!(
+      (p.total_rows, p.would_extract, p.skipped_filtered, p.skipped_empty_id, p.skipped_empty_body),
+      (5, 2, 1, 1, 1)
+    );
+  }
+
+  #[test]
+  fn preview_matches_extraction_count() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = mixed_rows_workbook(dir.path());
+    let p = preview_excel_units(path.to_string_lossy().to_string(), excel_config("Rows", "ID", &["Text"])).unwrap();
+    assert_eq!(p.would_extract, excel_units(&path, excel_config("Rows", "ID", &["Text"])).len());
+  }
+
+  /* ---------- .gitignore negation ---------- */
+
+  // Root-relative, '/'-separated paths from list_files
+  fn listed(root: &Path) -> Vec<String> {
+    list_files(root.to_string_lossy().to_string(), None, None, None).unwrap()
+      .iter()
+      .map(|p| rel_slash_path(root, Path::new(p)))
+      .collect()
+  }
+
+  #[test]
+  fn negated_subdirectory_of_ignored_dir_is_listed() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), ".gitignore", "node_modules/\n!node_modules/keep-me/\n");
+    write_file(dir.path(), "node_modules/drop/index.js", "x");
+    write_file(dir.path(), "node_modules/keep-me/index.js", "x");
+    write_file(dir.path(), "src/main.rs", "x");
+    // Tree order: directories first, then files
+    assert_eq!(listed(dir.path()), ["node_modules/keep-me/index.js", "src/main.rs", ".gitignore"]);
+  }
+
+  #[test]
+  fn ignored_dir_without_negation_stays_hidden() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), ".gitignore", "build/\n*.log\n!keep.log\n");
+    write_file(dir.path(), "build/out.bin", "x");
+    write_file(dir.path(), "debug.log", "x");
+    write_file(dir.path(), "keep.log", "x");
+    assert_eq!(listed(dir.path()), [".gitignore", "keep.log"]);
+  }
+
+  /* ---------- Symlinks in scans ---------- */
+
+  fn scan_tree(root: &Path, opts: ScanOptions) -> FileNode {
+    build_tree_with_gitignore(root, &opts, None).unwrap()
+  }
+
+  fn child<'a>(node: &'a FileNode, name: &str) -> &'a FileNode {
+    node.children.iter().flatten().find(|c| c.name == name).unwrap_or_else(|| panic!("no child {}", name))
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn symlink_is_a_leaf_when_not_following() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "sub/file.txt", "x");
+    std::os::unix::fs::symlink(dir.path().join("sub"), dir.path().join("link")).unwrap();
+    let tree = scan_tree(dir.path(), ScanOptions::default());
+    let link = child(&tree, "link");
+    assert!(link.is_symlink && !link.is_dir && link.children.is_none());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn self_referential_symlink_does_not_recurse() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "sub/file.txt", "x");
+    std::os::unix::fs::symlink(dir.path().join("sub"), dir.path().join("sub/again")).unwrap();
+    let opts = ScanOptions { follow_symlinks: true, ..Default::default() };
+    let tree = scan_tree(dir.path(), opts);
+    let sub = child(&tree, "sub");
+    let again = child(sub, "again");
+    assert!(again.is_symlink && again.children.is_none());
+    assert_eq!(child(sub, "file.txt").size, Some(1));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn followed_symlink_to_a_directory_is_expanded() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "real/file.txt", "x");
+    std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("alias")).unwrap();
+    let opts = ScanOptions { follow_symlinks: true, ..Default::default() };
+    let tree = scan_tree(dir.path(), opts);
+    let alias = child(&tree, "alias");
+    assert!(alias.is_dir && alias.is_symlink);
+    assert_eq!(child(alias, "file.txt").name, "file.txt");
+  }
+
+  /* ---------- read_file_base64 ---------- */
+
+  #[test]
+  fn base64_round_trips_binary_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let bytes: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0, 255, 10, 13, 128];
+    let path = write_file(dir.path(), "pixel.png", &bytes);
+    let out = read_file_base64(path.clone(), None).unwrap();
+    assert_eq!(out.mime, "image/png");
+    assert_eq!(out.file_path, path);
+    assert_eq!(BASE64_STANDARD.decode(out.base64).unwrap(), bytes);
+  }
+
+  #[test]
+  fn base64_rejects_files_over_the_cap() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "big.bin", [7u8; 16]);
+    let err = read_file_base64(path, Some(8)).err().unwrap();
+    assert!(matches!(&err, CommandError::Config(m) if m.contains("16 bytes")), "{}", err);
+  }
+
+  #[test]
+  fn base64_missing_file_is_not_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("nope.pdf").to_string_lossy().to_string();
+    assert!(matches!(read_file_base64(missing, None), Err(CommandError::NotFound)));
+  }
+
+  /* ---------- FileValue statistics ---------- */
+
+  #[test]
+  fn file_value_counts_chars_lines_and_words() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "notes.txt", "alpha beta\ngamma\n\ndelta  epsilon zeta");
+    let v = read_one(&path, None);
+    assert_eq!((v.chars, v.lines, v.words), (37, 4, 6));
+  }
+
+  #[test]
+  fn empty_file_has_zero_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "empty.txt", "");
+    let v = read_one(&path, None);
+    assert_eq!((v.chars, v.lines, v.words), (0, 0, 0));
+  }
+
+  /* ---------- concat_files ---------- */
+
+  #[test]
+  fn concat_files_adds_headers_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = write_file(dir.path(), "a.txt", "first");
+    let b = write_file(dir.path(), "b.txt", "second");
+    let out = concat_files(vec![b.clone(), a.clone()], None, None).unwrap();
+    assert_eq!(out.text, format!("=== {} ===\nsecond\n\n=== {} ===\nfirst", b, a));
+    assert_eq!((out.files_included, out.truncated), (2, false));
+  }
+
+  #[test]
+  fn concat_files_uses_header_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = write_file(dir.path(), "a.txt", "first");
+    let out = concat_files(vec![a.clone()], None, Some("// {path}".into())).unwrap();
+    assert_eq!(out.text, format!("// {}\nfirst", a));
+  }
+
+  #[test]
+  fn concat_files_stops_at_byte_budget() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = write_file(dir.path(), "a.txt", "0123456789");
+    let b = write_file(dir.path(), "b.txt", "never read");
+    // The first file fits exactly; the second header no longer does
+    let out = concat_files(vec![a.clone(), b.clone()], Some(12), Some("#".into())).unwrap();
+    assert_eq!(out.text, "#\n0123456789");
+    assert_eq!((out.files_included, out.truncated), (1, true));
+    // A file longer than the remaining budget is cut
+    let out = concat_files(vec![a, b], Some(10), Some("#".into())).unwrap();
+    assert_eq!(out.text, "#\n01234567");
+    assert_eq!((out.files_included, out.truncated), (1, true));
+  }
+
+  /* ---------- Scan cache ---------- */
+
+  fn scan_with(app: &tauri::App<tauri::test::MockRuntime>, root: &Path) -> Vec<String> {
+    let tree = scan_dir(app.state(), root.to_string_lossy().to_string(), None, None, None, None).unwrap();
+    let mut files = Vec::new();
+    collect_file_paths(&tree, &mut files);
+    files.iter().map(|p| rel_slash_path(root, Path::new(p))).collect()
+  }
+
+  #[test]
+  fn scan_cache_is_invalidated_when_root_changes() {
+    let app = tauri::test::mock_app();
+    app.manage(ScanCache::default());
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.txt", "x");
+    assert_eq!(scan_with(&app, dir.path()), ["a.txt"]);
+
+    // Let the directory mtime tick past the cached one
+    std::thread::sleep(Duration::from_millis(20));
+    write_file(dir.path(), "b.txt", "x");
+    assert_eq!(scan_with(&app, dir.path()), ["a.txt", "b.txt"]);
+  }
+
+  #[test]
+  fn scan_cache_serves_stale_tree_until_cleared() {
+    let app = tauri::test::mock_app();
+    app.manage(ScanCache::default());
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "sub/a.txt", "x");
+    assert_eq!(scan_with(&app, dir.path()), ["sub/a.txt"]);
+
+    // A nested edit leaves the root mtime alone, so the cached tree comes back
+    write_file(dir.path(), "sub/b.txt", "x");
+    assert_eq!(scan_with(&app, dir.path()), ["sub/a.txt"]);
+
+    clear_scan_cache(app.state()).unwrap();
+    assert_eq!(scan_with(&app, dir.path()), ["sub/a.txt", "sub/b.txt"]);
+  }
+
+  /* ---------- Regex blocks ---------- */
+
+  fn regex_config(delimiter: &str) -> RegexConfig {
+    serde_json::from_value(serde_json::json!({ "delimiter": delimiter })).unwrap()
+  }
+
+  const SECTIONS: &str = "## A\nalpha see 1.2\n\n## B\nbeta see 3.4 and 5.6\n\n## C\ngamma\n";
+
+  #[test]
+  fn regex_table_has_one_row_per_unit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", SECTIONS);
+    let mut config = regex_config(r"(?m)^## ");
+    config.id_capture = Some(r"^## (\w+)".into());
+    config.collect_captures = Some(r"(\d+\.\d+)".into());
+    let units = extract_regex_blocks(path.clone(), regex_config(r"(?m)^## ")).unwrap();
+    let table = regex_blocks_as_table(path, config).unwrap();
+
+    assert_eq!(table.rows.len(), units.len());
+    assert_eq!(&table.columns[..2], ["id", "body"]);
+    assert!(table.columns.iter().any(|c| c == "captures"));
+    let ids: Vec<&str> = table.rows.iter().map(|r| r["id"].as_str()).collect();
+    assert_eq!(ids, ["A", "B", "C"]);
+    assert_eq!(table.rows[1]["captures"], r#"["3.4","5.6"]"#);
+  }
+
+  /* ---------- maxUnits / minBodyLen ---------- */
+
+  #[test]
+  fn regex_blocks_respect_min_body_len_and_max_units() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", "## A\nlong enough\n## B\nx\n## C\nalso long\n## D\nlong as well\n");
+    let mut config = regex_config(r"(?m)^## ");
+    config.id_capture = Some(r"^## (\w+)".into());
+    config.min_body_len = Some(8);
+    config.max_units = Some(2);
+    let units = extract_regex_blocks(path, config).unwrap();
+    assert_eq!(ids(&units), ["A", "C"]);
+  }
+
+  #[test]
+  fn html_blocks_respect_min_body_len_and_max_units() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html",
+      r#"<div class="c" id="a">long enough</div><div class="c" id="b">x</div><div class="c" id="c">also long</div><div class="c" id="d">long as well</div>"#);
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({
+      "itemSelector": ".c", "minBodyLen": 8, "maxUnits": 2,
+    })).unwrap();
+    let units = extract_html_blocks(path, config).unwrap();
+    assert_eq!(ids(&units), ["a", "c"]);
+  }
+
+  fn limits_workbook(dir: &Path) -> PathBuf {
+    let path = dir.join("limits.xlsx");
+    write_xlsx(&path, &[("Rows", vec![
+      vec!["ID", "Text"],
+      vec!["a", "long enough"],
+      vec!["b", "x"],
+      vec!["c", "also long"],
+      vec!["d", "long as well"],
+    ])], &[]);
+    path
+  }
+
+  #[test]
+  fn excel_units_respect_min_body_len_and_max_units() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = limits_workbook(dir.path());
+    let mut config = excel_config("Rows", "ID", &["Text"]);
+    config.min_body_len = Some(8);
+    config.max_units = Some(2);
+    assert_eq!(ids(&excel_units(&path, config)), ["a", "c"]);
+  }
+
+  #[test]
+  fn preview_applies_min_body_len_and_max_units() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = limits_workbook(dir.path());
+    let mut config = excel_config("Rows", "ID", &["Text"]);
+    config.min_body_len = Some(8);
+    config.max_units = Some(2);
+    let p = preview_excel_units(path.to_string_lossy().to_string(), config).unwrap();
+    assert_eq!((p.total_rows, p.would_extract, p.skipped_too_short, p.skipped_over_max), (4, 2, 1, 1));
+  }
+
+  /* ---------- formula text ---------- */
+
+  fn formula_workbook(dir: &Path) -> PathBuf {
+    let path = dir.join("formulas.xlsx");
+    write_xlsx(&path, &[("Calc", vec![
+      vec!["ID", "Value"],
+      vec!["sum", "=SUM(1,2)->3"],
+      vec!["plain", "literal"],
+    ])], &[]);
+    path
+  }
+
+  #[test]
+  fn formula_cells_give_cached_values_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = formula_workbook(dir.path());
+    let units = excel_units(&path, excel_config("Calc", "ID", &["Value"]));
+    let bodies: Vec<&str> = units.iter().map(|u| u.body.as_str()).collect();
+    assert_eq!(bodies, ["3", "literal"]);
+  }
+
+  #[test]
+  fn use_formula_text_emits_formulas() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = formula_workbook(dir.path());
+    let mut config = excel_config("Calc", "ID", &["Value"]);
+    config.use_formula_text = Some(true);
+    let units = excel_units(&path, config);
+    let bodies: Vec<&str> = units.iter().map(|u| u.body.as_str()).collect();
+    // Cells without a formula still give their value
+    assert_eq!(bodies, ["=SUM(1,2)", "literal"]);
+  }
+
+  /* ---------- idMode ---------- */
+
+  #[test]
+  fn hash_ids_are_stable_across_runs_and_shared_by_equal_bodies() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.txt", "abc\n  abc  \nother\n");
+    let run = || {
+      // One block per line
+      let mut config = regex_config(r"(?m)^");
+      config.id_mode = Some("hash".into());
+      extract_regex_blocks(path.clone(), config).unwrap()
+    };
+    let (first, second) = (run(), run());
+    assert_eq!(ids(&first), ids(&second));
+    // First 12 hex chars of sha256("abc"); surrounding whitespace is ignored
+    assert_eq!(ids(&first)[..2], ["ba7816bf8f01", "ba7816bf8f01"]);
+    assert_ne!(first[2].id, first[0].id);
+  }
+
+  #[test]
+  fn html_hash_ids_match_regex_hash_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", r#"<p class="u" id="x">abc</p>"#);
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({
+      "itemSelector": ".u", "idMode": "hash",
+    })).unwrap();
+    assert_eq!(ids(&extract_html_blocks(path, config).unwrap()), ["ba7816bf8f01"]);
+  }
+
+  #[test]
+  fn sequential_ids_and_unknown_id_mode() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", SECTIONS);
+    let mut config = regex_config(r"(?m)^## ");
+    config.id_capture = Some(r"^## (\w+)".into());
+    config.id_mode = Some("sequential".into());
+    assert_eq!(ids(&extract_regex_blocks(path.clone(), config).unwrap()), ["1", "2", "3"]);
+
+    let mut config = regex_config(r"(?m)^## ");
+    config.id_mode = Some("random".into());
+    assert!(matches!(extract_regex_blocks(path, config), Err(CommandError::Config(_))));
+  }
+
+  /* ---------- mock HTTP server ---------- */
+
+  #[derive(Clone, Debug)]
+  struct SeenRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,   // names lower-cased
+    body: String,
+  }
+
+  impl SeenRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+      self.headers.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+  }
+
+  fn http_response(status: &str, headers: &[(&str, &str)], body: impl AsRef<[u8]>) -> Vec<u8> {
+    let body = body.as_ref();
+    let mut out = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+    for (k, v) in headers {
+      out.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    out.push_str("\r\n");
+    let mut out = out.into_bytes();
+    out.extend_from_slice(body);
+    out
+  }
+
+  fn read_request(stream: &mut std::net::TcpStream) -> Option<SeenRequest> {
+    use std::io::{BufRead, BufReader, Read};
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = (parts.next()?.to_string(), parts.next()?.to_string());
+    let mut headers = Vec::new();
+    loop {
+      let mut h = String::new();
+      reader.read_line(&mut h).ok()?;
+      let h = h.trim_end();
+      if h.is_empty() { break; }
+      if let Some((k, v)) = h.split_once(':') {
+        headers.push((k.trim().to_lowercase(), v.trim().to_string()));
+      }
+    }
+    let len = headers.iter().find(|(k, _)| k == "content-length").and_then(|(_, v)| v.parse().ok()).unwrap_or(0);
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).ok()?;
+    Some(SeenRequest { method, path, headers, body: String::from_utf8_lossy(&body).into_owned() })
+  }
+
+  // Serves every connection with `respond` on a background thread; returns the
+  // base URL ("http://127.0.0.1:port") and the requests seen so far
+  fn mock_server(
+    respond: impl Fn(&SeenRequest) -> Vec<u8> + Send + Sync + 'static,
+  ) -> (String, std::sync::Arc<Mutex<Vec<SeenRequest>>>) {
+    use std::io::Write;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let seen = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let respond = std::sync::Arc::new(respond);
+    let log = seen.clone();
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue; };
+        let (respond, log) = (respond.clone(), log.clone());
+        std::thread::spawn(move || {
+          if let Some(req) = read_request(&mut stream) {
+            log.lock().unwrap().push(req.clone());
+            let _ = stream.write_all(&respond(&req));
+          }
+        });
+      }
+    });
+    (base, seen)
+  }
+
+  /* ---------- non-JSON API responses ---------- */
+
+  fn html_error_page(_: &SeenRequest) -> Vec<u8> {
+    http_response("200 OK", &[("Content-Type", "text/html; charset=utf-8")], "<html><body>Internal error</body></html>")
+  }
+
+  fn assert_non_json_error<T>(result: Result<T, CommandError>) {
+    match result {
+      Err(CommandError::Parse(msg)) => {
+        assert!(msg.contains("content-type: text/html; charset=utf-8"), "{}", msg);
+        assert!(msg.contains("Body starts with: <html><body>Internal error"), "{}", msg);
+      }
+      Err(other) => panic!("expected a Parse error, got {:?}", other),
+      Ok(_) => panic!("expected a Parse error"),
+    }
+  }
+
+  #[tokio::test]
+  async fn fetch_api_table_reports_html_body_and_content_type() {
+    let (base, seen) = mock_server(html_error_page);
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", "<p>hi</p>");
+    assert_non_json_error(fetch_api_table(format!("{}/extract", base), path, None, None, None, None).await);
+
+    let seen = seen.lock().unwrap();
+    assert_eq!((seen[0].method.as_str(), seen[0].path.as_str()), ("POST", "/extract"));
+    assert_eq!(seen[0].header("content-type"), Some("application/json"));
+    assert_eq!(seen[0].body, r#"{"data":"<p>hi</p>"}"#);
+  }
+
+  #[tokio::test]
+  async fn fetch_api_table_from_url_reports_html_body_and_content_type() {
+    let (base, _) = mock_server(html_error_page);
+    let result = fetch_api_table_from_url(format!("{}/extract", base), format!("{}/page", base), None, None, None, None, None).await;
+    assert_non_json_error(result);
+  }
+
+  #[tokio::test]
+  async fn extract_api_units_reports_html_body_and_content_type() {
+    let (base, _) = mock_server(html_error_page);
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", "<p>hi</p>");
+    assert_non_json_error(extract_api_units(format!("{}/extract", base), path, "items".into(), None, None, None).await);
+  }
+
+  /* ---------- user agent ---------- */
+
+  // GET serves a small page; POST answers like the extraction API
+  fn page_and_api(req: &SeenRequest) -> Vec<u8> {
+    if req.method == "GET" {
+      http_response("200 OK", &[("Content-Type", "text/html")], "<p>page</p>")
+    } else {
+      http_response("200 OK", &[("Content-Type", "application/json")], r#"[{"code":"1","text":"one"}]"#)
+    }
+  }
+
+  #[tokio::test]
+  async fn custom_user_agent_reaches_the_server() {
+    let (base, seen) = mock_server(page_and_api);
+    let table = fetch_api_table_from_url(
+      format!("{}/extract", base), format!("{}/page", base), Some("my-client/2.0".into()), None, None, None, None,
+    ).await.unwrap();
+    assert_eq!(table.rows.len(), 1);
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    assert!(seen.iter().all(|r| r.header("user-agent") == Some("my-client/2.0")));
+  }
+
+  #[tokio::test]
+  async fn user_agent_defaults_to_browser_string() {
+    let (base, seen) = mock_server(page_and_api);
+    fetch_api_table_from_url(format!("{}/extract", base), format!("{}/page", base), None, None, None, None, None)
+      .await.unwrap();
+    assert_eq!(seen.lock().unwrap()[0].header("user-agent"), Some(BROWSER_USER_AGENT));
+  }
+
+  /* ---------- retryUrlRewrite ---------- */
+
+  // Pages under /v2/ carry the marker; the API echoes the posted text back as one row
+  fn versioned_pages(req: &SeenRequest) -> Vec<u8> {
+    match req.method.as_str() {
+      "GET" if req.path.starts_with("/v2/") => http_response("200 OK", &[], "<p class=\"ready\">new</p>"),
+      "GET" => http_response("200 OK", &[], "<p>old</p>"),
+      _ => {
+        let posted: Value = serde_json::from_str(&req.body).unwrap();
+        http_response("200 OK", &[("Content-Type", "application/json")], serde_json::json!([{ "page": posted["data"] }]).to_string())
+      }
+    }
+  }
+
+  fn v1_to_v2() -> UrlRewrite {
+    UrlRewrite { from: "/v1/".into(), to: "/v2/".into(), success_marker: "class=\"ready\"".into() }
+  }
+
+  #[tokio::test]
+  async fn rewrite_is_used_when_marker_is_missing() {
+    let (base, seen) = mock_server(versioned_pages);
+    let table = fetch_api_table_from_url(
+      format!("{}/extract", base), format!("{}/v1/doc", base), None, Some(v1_to_v2()), None, None, None,
+    ).await.unwrap();
+    assert_eq!(table.rows[0]["page"], "<p class=\"ready\">new</p>");
+    let paths: Vec<String> = seen.lock().unwrap().iter().map(|r| r.path.clone()).collect();
+    assert_eq!(paths, ["/v1/doc", "/v2/doc", "/extract"]);
+  }
+
+  #[tokio::test]
+  async fn rewrite_is_skipped_when_url_does_not_match() {
+    let (base, seen) = mock_server(versioned_pages);
+    let table = fetch_api_table_from_url(
+      format!("{}/extract", base), format!("{}/other/doc", base), None, Some(v1_to_v2()), None, None, None,
+    ).await.unwrap();
+    assert_eq!(table.rows[0]["page"], "<p>old</p>");
+    assert_eq!(seen.lock().unwrap().len(), 2);
+  }
+
+  /* ---------- compressed responses ---------- */
+
+  fn gzip(data: &str) -> Vec<u8> {
+    use std::io::Write;
+    let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    enc.write_all(data.as_bytes()).unwrap();
+    enc.finish().unwrap()
+  }
+
+  const GZIP_PAGE: &str = r#"<div class="u" id="z">zipped body</div>"#;
+
+  #[tokio::test]
+  async fn gzip_encoded_page_is_decoded() {
+    let (base, seen) = mock_server(|_| http_response("200 OK", &[("Content-Type", "text/html"), ("Content-Encoding", "gzip")], gzip(GZIP_PAGE)));
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({ "itemSelector": ".u" })).unwrap();
+    let units = extract_html_blocks_from_url(format!("{}/page", base), config, None, None, None, None, None).await.unwrap();
+    assert_eq!((units[0].id.as_str(), units[0].body.as_str()), ("z", "zipped body"));
+    assert!(seen.lock().unwrap()[0].header("accept-encoding").is_some_and(|v| v.contains("gzip")));
+  }
+
+  #[tokio::test]
+  async fn gzip_body_without_content_encoding_is_a_parse_error() {
+    let (base, _) = mock_server(|_| http_response("200 OK", &[("Content-Type", "text/html")], gzip(GZIP_PAGE)));
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({ "itemSelector": ".u" })).unwrap();
+    let result = extract_html_blocks_from_url(format!("{}/page", base), config, None, None, None, None, None).await;
+    assert!(matches!(result, Err(CommandError::Parse(m)) if m.contains("compressed body")));
+  }
+
+  /* ---------- sheet row counts ---------- */
+
+  #[test]
+  fn inspect_excel_reports_row_counts_per_sheet() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("multi.xlsx");
+    write_xlsx(&path, &[
+      ("Three", vec![vec!["ID", "Text"], vec!["a", "x"], vec!["b", "y"]]),
+      ("Empty", vec![]),
+      ("One", vec![vec!["ID"]]),
+    ], &[]);
+    let info = inspect_excel(path.to_string_lossy().to_string()).unwrap();
+    let counts: Vec<(&str, usize)> = info.sheets.iter().map(|s| (s.name.as_str(), s.row_count)).collect();
+    // Counts include the header row
+    assert_eq!(counts, [("Three", 3), ("Empty", 0), ("One", 1)]);
+  }
+
+  /* ---------- "#N" column indices ---------- */
+
+  fn duplicate_header_workbook(dir: &Path) -> PathBuf {
+    let path = dir.join("dupes.xlsx");
+    write_xlsx(&path, &[("Items", vec![
+      vec!["Code", "Description", "Description"],
+      vec!["a1", "short", "the long one"],
+    ])], &[]);
+    path
+  }
+
+  #[test]
+  fn index_tokens_pick_duplicate_headers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = duplicate_header_workbook(dir.path());
+    let by_name = excel_units(&path, excel_config("Items", "Code", &["Description"]));
+    assert_eq!(by_name[0].body, "short");
+    let by_index = excel_units(&path, excel_config("Items", "#0", &["#2"]));
+    assert_eq!((by_index[0].id.as_str(), by_index[0].body.as_str()), ("a1", "the long one"));
+  }
+
+  #[test]
+  fn index_tokens_past_the_header_are_config_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = duplicate_header_workbook(dir.path()).to_string_lossy().to_string();
+    let result = extract_excel_units(path.clone(), excel_config("Items", "#3", &["#1"]));
+    assert!(matches!(result, Err(CommandError::Config(m)) if m == "ID column not found: #3"));
+    let result = extract_excel_units(path, excel_config("Items", "#0", &["#99"]));
+    assert!(matches!(result, Err(CommandError::Config(m)) if m == "Description column not found: #99"));
+  }
+
+  /* ---------- normalizeWhitespace ---------- */
+
+  const MESSY: &str = "first line   \n\n\n\nsecond\t\n\nthird";
+  const TIDY: &str = "first line\n\nsecond\n\nthird";
+
+  #[test]
+  fn normalize_whitespace_collapses_blank_runs_and_trailing_space() {
+    assert_eq!(normalize_whitespace(MESSY), TIDY);
+    assert_eq!(normalize_whitespace("a\r\n\r\n\r\nb  \r\n"), "a\n\nb");
+  }
+
+  #[test]
+  fn regex_blocks_normalize_whitespace_only_when_asked() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.txt", MESSY);
+    let plain = extract_regex_blocks(path.clone(), regex_config("@@")).unwrap();
+    assert_eq!(plain[0].body, MESSY);
+    let mut config = regex_config("@@");
+    config.normalize_whitespace = Some(true);
+    assert_eq!(extract_regex_blocks(path, config).unwrap()[0].body, TIDY);
+  }
+
+  #[test]
+  fn html_and_excel_normalize_whitespace() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", format!("<pre class=\"u\" id=\"p\">{}</pre>", MESSY));
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({ "itemSelector": ".u", "normalizeWhitespace": true })).unwrap();
+    assert_eq!(extract_html_blocks(path, config).unwrap()[0].body, TIDY);
+
+    let path = dir.path().join("messy.xlsx");
+    write_xlsx(&path, &[("Notes", vec![vec!["ID", "Text"], vec!["n1", MESSY]])], &[]);
+    let mut config = excel_config("Notes", "ID", &["Text"]);
+    config.normalize_whitespace = Some(true);
+    assert_eq!(excel_units(&path, config)[0].body, TIDY);
+  }
+
+  /* ---------- CommandError shape ---------- */
+
+  #[test]
+  fn missing_file_serializes_as_not_found_without_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("nope.md").to_string_lossy().to_string();
+    let err = extract_regex_blocks(missing, regex_config("##")).err().unwrap();
+    assert_eq!(serde_json::to_value(&err).unwrap(), serde_json::json!({ "kind": "NotFound" }));
+  }
+
+  #[test]
+  fn config_error_serializes_kind_and_message() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", SECTIONS);
+    let err = extract_regex_blocks(path, regex_config("(unclosed")).err().unwrap();
+    let v = serde_json::to_value(&err).unwrap();
+    assert_eq!(v["kind"], "Config");
+    assert!(v["message"].as_str().is_some_and(|m| m.contains("unclosed")));
+  }
+
+  #[test]
+  fn unparseable_workbook_is_a_parse_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "broken.xlsx", "not a zip");
+    let err = inspect_excel(path).err().unwrap();
+    assert_eq!(serde_json::to_value(&err).unwrap()["kind"], "Parse");
+  }
+
+  /* ---------- preview_excel ---------- */
+
+  #[test]
+  fn preview_excel_returns_at_most_limit_rows_under_the_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rows.xlsx");
+    let mut rows = vec![vec![""], vec!["ID", "Name", "Name"]];
+    rows.extend((0..30).map(|_| vec!["x", "y", "z"]));
+    write_xlsx(&path, &[("Data", rows)], &[]);
+    let path = path.to_string_lossy().to_string();
+
+    let table = preview_excel(path.clone(), "Data".into(), Some(3), None).unwrap();
+    assert_eq!(table.columns, ["ID", "Name", "Name (2)"]);
+    assert_eq!(table.rows.len(), 3);
+    assert_eq!(table.rows[0]["Name (2)"], "z");
+    // Default limit is 20
+    assert_eq!(preview_excel(path, "Data".into(), None, None).unwrap().rows.len(), 20);
+  }
+
+  /* ---------- grouped item selectors ---------- */
+
+  #[test]
+  fn grouped_item_selector_keeps_document_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", r#"
+      <div class="tile" id="t1">one</div>
+      <div class="card" id="c1">two</div>
+      <section><div class="tile" id="t2">three</div></section>
+      <div class="card tile" id="both">four</div>
+      <div class="card" id="c2">five</div>
+    "#);
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({ "itemSelector": ".card, .tile" })).unwrap();
+    let units = extract_html_blocks(path, config).unwrap();
+    // An element matching both selectors is returned once
+    assert_eq!(ids(&units), ["t1", "c1", "t2", "both", "c2"]);
+  }
+
+  /* ---------- legacy encodings ---------- */
+
+  const ACCENTED: &str = "## Café\nLe garçon a déjà payé “deux” crèmes brûlées.\n## Noël\nÀ bientôt, señor.\n";
+
+  fn utf16le(text: &str, bom: bool) -> Vec<u8> {
+    let mut out = if bom { vec![0xFF, 0xFE] } else { Vec::new() };
+    out.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    out
+  }
+
+  fn windows_1252(text: &str) -> Vec<u8> {
+    let (bytes, _, unmappable) = encoding_rs::WINDOWS_1252.encode(text);
+    assert!(!unmappable);
+    bytes.into_owned()
+  }
+
+  fn section_units(path: String) -> Vec<PromptUnit> {
+    let mut config = regex_config(r"(?m)^## ");
+    config.id_capture = Some(r"^## (\S+)".into());
+    extract_regex_blocks(path, config).unwrap()
+  }
+
+  #[test]
+  fn regex_blocks_decode_utf16le_with_and_without_bom() {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, bom) in [("bom.txt", true), ("plain.txt", false)] {
+      let units = section_units(write_file(dir.path(), name, utf16le(ACCENTED, bom)));
+      assert_eq!(ids(&units), ["Café", "Noël"], "{}", name);
+      assert_eq!(units[0].body, "## Café\nLe garçon a déjà payé “deux” crèmes brûlées.");
+    }
+  }
+
+  #[test]
+  fn regex_blocks_decode_windows_1252() {
+    let dir = tempfile::tempdir().unwrap();
+    let units = section_units(write_file(dir.path(), "legacy.txt", windows_1252(ACCENTED)));
+    assert_eq!(ids(&units), ["Café", "Noël"]);
+    assert_eq!(units[1].body, "## Noël\nÀ bientôt, señor.");
+  }
+
+  #[test]
+  fn html_blocks_decode_legacy_encodings() {
+    let dir = tempfile::tempdir().unwrap();
+    let page = "<p class=\"u\" id=\"a\">Le garçon a déjà payé “deux” crèmes brûlées à Noël.</p>";
+    let config = || -> HtmlConfig { serde_json::from_value(serde_json::json!({ "itemSelector": ".u" })).unwrap() };
+    for (name, bytes) in [("utf16.html", utf16le(page, true)), ("cp1252.html", windows_1252(page))] {
+      let units = extract_html_blocks(write_file(dir.path(), name, bytes), config()).unwrap();
+      assert_eq!(units[0].body, "Le garçon a déjà payé “deux” crèmes brûlées à Noël.", "{}", name);
+    }
+  }
+
+  /* ---------- scan_summary ---------- */
+
+  #[test]
+  fn scan_summary_counts_visible_files_and_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_file(root, ".gitignore", "target/\n");           // 8 bytes
+    write_file(root, "a.txt", "hello");                     // 5 bytes
+    fs::create_dir_all(root.join("src/empty")).unwrap();
+    write_file(&root.join("src"), "b.rs", "fn main(){}");  // 11 bytes
+    fs::create_dir_all(root.join("target")).unwrap();
+    write_file(&root.join("target"), "big.bin", vec![0u8; 1000]);
+    fs::create_dir_all(root.join(".git")).unwrap();
+    write_file(&root.join(".git"), "HEAD", "ref: x");
+
+    let path = root.to_string_lossy().to_string();
+    let sum = scan_summary(path.clone(), None).unwrap();
+    assert_eq!((sum.file_count, sum.dir_count, sum.total_bytes), (3, 2, 24));
+
+    let sum = scan_summary(path, Some(false)).unwrap();
+    assert_eq!((sum.file_count, sum.dir_count, sum.total_bytes), (4, 3, 1024));
+  }
+
+  /* ---------- pathRegex ---------- */
+
+  #[test]
+  fn path_regex_keeps_matching_files_and_prunes_empty_dirs() {
+    let app = tauri::test::mock_app();
+    app.manage(ScanCache::default());
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    for f in ["src/main.rs", "src/readme.md", "src/inner/x.rs", "src/assets/logo.svg", "docs/a.md", "top.rs"] {
+      let p = root.join(f);
+      fs::create_dir_all(p.parent().unwrap()).unwrap();
+      fs::write(p, "x").unwrap();
+    }
+    let path = root.to_string_lossy().to_string();
+
+    let tree = scan_dir(app.state(), path.clone(), None, Some(r"^src/.*\.rs$".into()), None, None).unwrap();
+    let mut files = Vec::new();
+    collect_file_paths(&tree, &mut files);
+    let rel: Vec<String> = files.iter().map(|p| rel_slash_path(root, Path::new(p))).collect();
+    assert_eq!(rel, ["src/inner/x.rs", "src/main.rs"]);
+    let top: Vec<&str> = tree.children.iter().flatten().map(|c| c.name.as_str()).collect();
+    assert_eq!(top, ["src"]);
+    let src: Vec<&str> = child(&tree, "src").children.iter().flatten().map(|c| c.name.as_str()).collect();
+    assert_eq!(src, ["inner", "main.rs"]);
+
+    let bad = scan_dir(app.state(), path, None, Some("(".into()), None, None);
+    assert!(matches!(bad, Err(CommandError::Config(_))));
+  }
+
+  /* ---------- extract_excel_units_batch ---------- */
+
+  fn two_sheet_workbook(dir: &Path) -> PathBuf {
+    let path = dir.join("two.xlsx");
+    write_xlsx(&path, &[
+      ("Items", vec![vec!["ID", "Text"], vec!["i1", "first item"], vec!["i2", "second item"]]),
+      ("Notes", vec![vec!["Key", "Note"], vec!["n1", "a note"]]),
+    ], &[]);
+    path
+  }
+
+  #[test]
+  fn batch_runs_each_config_against_one_workbook() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = two_sheet_workbook(dir.path()).to_string_lossy().to_string();
+    let configs = vec![excel_config("Notes", "Key", &["Note"]), excel_config("Items", "ID", &["Text"])];
+    let Ok(ExcelBatchResult::Results(results)) = extract_excel_units_batch(path, configs, None) else {
+      panic!("expected uncapped results");
+    };
+    let per_config: Vec<Vec<String>> = results.into_iter()
+      .map(|r| r.into_units().into_iter().map(|u| u.id).collect())
+      .collect();
+    assert_eq!(per_config, [vec!["n1"], vec!["i1", "i2"]]);
+  }
+
+  /* ---------- extract_json_units ---------- */
+
+  fn json_config(array_path: Option<&str>) -> JsonConfig {
+    serde_json::from_value(serde_json::json!({
+      "idField": "id",
+      "bodyFields": ["title", "detail.text"],
+      "arrayPath": array_path,
+    })).unwrap()
+  }
+
+  #[test]
+  fn json_array_under_a_dotted_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "data.json", r#"{ "data": { "items": [
+      { "id": 7, "title": "Seven", "detail": { "text": "lucky" } },
+      { "id": "", "title": "no id" },
+      { "id": "x", "title": "  Only title  " }
+    ] } }"#);
+    let units = extract_json_units(path, json_config(Some("data.items"))).unwrap();
+    let got: Vec<(&str, &str)> = units.iter().map(|u| (u.id.as_str(), u.body.as_str())).collect();
+    assert_eq!(got, [("7", "Seven\nlucky"), ("x", "Only title")]);
+    assert_eq!(units[1].meta, Some(serde_json::json!({ "recordIndex": 2 })));
+  }
+
+  #[test]
+  fn jsonl_lines_are_records() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "data.jsonl",
+      "{\"id\":\"a\",\"title\":\"Alpha\"}\n\n{\"id\":\"b\",\"detail\":{\"text\":\"Beta\"}}\n");
+    let units = extract_json_units(path, json_config(None)).unwrap();
+    assert_eq!(ids(&units), ["a", "b"]);
+    assert_eq!(units[1].body, "Beta");
+  }
+
+  #[test]
+  fn single_jsonl_record_is_not_unwrapped_into_nested_objects() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "data.jsonl",
+      "{\"id\":\"a\",\"title\":\"Alpha\",\"tags\":[{\"id\":\"x\",\"title\":\"tag\"}]}\n");
+    let units = extract_json_units(path, json_config(None)).unwrap();
+    assert_eq!(ids(&units), ["a"]);
+    assert_eq!(units[0].body, "Alpha");
+  }
+
+  #[test]
+  fn bad_jsonl_line_reports_its_number() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "data.jsonl", "{\"id\":\"a\"}\n{oops\n");
+    let result = extract_json_units(path, json_config(None));
+    assert!(matches!(result, Err(CommandError::Parse(m)) if m.starts_with("Line 2:")));
+  }
+
+  /* ---------- extract_paragraphs ---------- */
+
+  const PARAGRAPHS: &str = "First paragraph\nspans two lines.\n\n\n  Hi.  \n \t\nThird paragraph here.\n";
+
+  #[test]
+  fn paragraphs_split_on_blank_lines_with_sequential_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "notes.txt", PARAGRAPHS);
+    let units = extract_paragraphs(path.clone(), None).unwrap();
+    let got: Vec<(&str, &str)> = units.iter().map(|u| (u.id.as_str(), u.body.as_str())).collect();
+    assert_eq!(got, [("1", "First paragraph\nspans two lines."), ("2", "Hi."), ("3", "Third paragraph here.")]);
+    assert_eq!(units[0].meta, Some(serde_json::json!({ "sourcePath": path })));
+  }
+
+  #[test]
+  fn paragraphs_under_min_len_are_dropped_without_gaps_in_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "notes.txt", PARAGRAPHS);
+    let units = extract_paragraphs(path, Some(5)).unwrap();
+    let got: Vec<(&str, &str)> = units.iter().map(|u| (u.id.as_str(), u.body.as_str())).collect();
+    assert_eq!(got, [("1", "First paragraph\nspans two lines."), ("2", "Third paragraph here.")]);
+  }
+
+  /* ---------- id prefixes ---------- */
+
+  #[test]
+  fn sheet_name_prefix_keeps_ids_unique_across_sheets() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clash.xlsx");
+    write_xlsx(&path, &[
+      ("Items", vec![vec!["ID", "Text"], vec!["1", "item one"]]),
+      ("Notes", vec![vec!["ID", "Text"], vec!["1", "note one"]]),
+    ], &[]);
+    let configs = ["Items", "Notes"].map(|sheet| {
+      let mut c = excel_config(sheet, "ID", &["Text"]);
+      c.prefix_sheet_name = Some(true);
+      c.id_prefix = Some("kb/".into());
+      c
+    });
+    let Ok(ExcelBatchResult::Results(results)) = extract_excel_units_batch(path.to_string_lossy().to_string(), configs.into(), None) else {
+      panic!("expected uncapped results");
+    };
+    let all: Vec<String> = results.into_iter().flat_map(|r| r.into_units()).map(|u| u.id).collect();
+    // id_prefix goes in front of the sheet name
+    assert_eq!(all, ["kb/Items:1", "kb/Notes:1"]);
+  }
+
+  #[test]
+  fn id_prefix_alone_leaves_sheet_name_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = two_sheet_workbook(dir.path());
+    let mut config = excel_config("Items", "ID", &["Text"]);
+    config.id_prefix = Some("item-".into());
+    assert_eq!(ids(&excel_units(&path, config)), ["item-i1", "item-i2"]);
+  }
+
+  /* ---------- table_to_units ---------- */
+
+  fn table(columns: &[&str], rows: &[&[(&str, &str)]]) -> ApiTable {
+    ApiTable {
+      columns: columns.iter().map(|c| c.to_string()).collect(),
+      rows: rows.iter().map(|r| r.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()).collect(),
+      raw: None,
+    }
+  }
+
+  #[test]
+  fn table_rows_become_units() {
+    let t = table(&["code", "title", "text"], &[
+      &[("code", "A1"), ("title", "Alpha"), ("text", "first")],
+      &[("title", "Beta"), ("text", "  ")],
+    ]);
+    let units = table_to_units(t, "code".into(), vec!["title".into(), "text".into()], Some(" | ".into()), None).unwrap();
+    let got: Vec<(&str, &str)> = units.iter().map(|u| (u.id.as_str(), u.body.as_str())).collect();
+    // The second row has no code, so it falls back to its 1-based row number
+    assert_eq!(got, [("A1", "Alpha | first"), ("2", "Beta")]);
+    assert_eq!(units[1].meta, Some(serde_json::json!({ "rowIndex": 1 })));
+  }
+
+  #[test]
+  fn table_rows_without_body_are_skipped_and_separator_defaults_to_newline() {
+    let t = table(&["id", "a", "b"], &[
+      &[("id", "x"), ("a", "one"), ("b", "two")],
+      &[("id", "y")],
+    ]);
+    let units = table_to_units(t, "id".into(), vec!["a".into(), "b".into()], None, None).unwrap();
+    assert_eq!(ids(&units), ["x"]);
+    assert_eq!(units[0].body, "one\ntwo");
+  }
+
+  /* ---------- maxResponseBytes ---------- */
+
+  fn big_json() -> String {
+    serde_json::json!([{ "text": "x".repeat(10_000) }]).to_string()
+  }
+
+  #[tokio::test]
+  async fn response_over_cap_is_rejected() {
+    let (base, _) = mock_server(|_| http_response("200 OK", &[("Content-Type", "application/json")], big_json()));
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", "<p>hi</p>");
+    let result = fetch_api_table(format!("{}/extract", base), path.clone(), Some(1000), None, None, None).await;
+    assert!(matches!(result, Err(CommandError::Network(m)) if m.ends_with("exceeds the 1000 byte limit")));
+    // The same body fits the default cap
+    assert!(fetch_api_table(format!("{}/extract", base), path, None, None, None, None).await.is_ok());
+  }
+
+  #[tokio::test]
+  async fn body_without_content_length_is_capped_while_streaming() {
+    // No Content-Length: the body runs until the connection closes
+    let (base, _) = mock_server(|req| {
+      let body = if req.method == "GET" { "<p>page</p>".to_string() } else { big_json() };
+      format!("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{}", body).into_bytes()
+    });
+    let result = fetch_api_table_from_url(
+      format!("{}/extract", base), format!("{}/page", base), None, None, Some(1000), None, None,
+    ).await;
+    assert!(matches!(result, Err(CommandError::Network(m)) if m.contains("/extract exceeds the 1000 byte limit")));
+  }
+
+  /* ---------- includeHtml ---------- */
+
+  #[test]
+  fn include_html_puts_inner_markup_in_meta() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", r#"<div class="u" id="a"><b>x</b></div>"#);
+    let config = |include: bool| -> HtmlConfig {
+      serde_json::from_value(serde_json::json!({ "itemSelector": ".u", "includeHtml": include })).unwrap()
+    };
+    let units = extract_html_blocks(path.clone(), config(true)).unwrap();
+    assert_eq!(units[0].body, "x");
+    assert_eq!(units[0].meta.as_ref().unwrap()["html"], "<b>x</b>");
+
+    let units = extract_html_blocks(path, config(false)).unwrap();
+    assert!(units[0].meta.as_ref().and_then(|m| m.get("html")).is_none());
+  }
+
+  /* ---------- search_files matching options ---------- */
+
+  fn search_lines(root: &Path, pattern: &str, case_insensitive: bool, whole_word: bool) -> Vec<String> {
+    let result = search_files(
+      root.to_string_lossy().to_string(), pattern.into(), Some(case_insensitive), Some(whole_word), None, None, None, None,
+    ).unwrap();
+    let SearchResult::Hits(hits) = result else { panic!("expected ungrouped hits") };
+    hits.into_iter().map(|h| h.line).collect()
+  }
+
+  const CATS: &str = "cat\nCat nap\nconcatenate\nthe CAT.\nbobcats\n";
+
+  #[test]
+  fn search_is_case_sensitive_unless_asked() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.txt", CATS);
+    assert_eq!(search_lines(dir.path(), "cat", false, false), ["cat", "concatenate", "bobcats"]);
+    assert_eq!(search_lines(dir.path(), "cat", true, false), ["cat", "Cat nap", "concatenate", "the CAT.", "bobcats"]);
+  }
+
+  #[test]
+  fn whole_word_wraps_literals_only() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.txt", CATS);
+    assert_eq!(search_lines(dir.path(), "cat", true, true), ["cat", "Cat nap", "the CAT."]);
+    // A regex is used as written, so "cats?" still matches inside words
+    assert_eq!(search_lines(dir.path(), "cats?", false, true), ["cat", "concatenate", "bobcats"]);
+  }
+
+  /* ---------- search context ---------- */
+
+  fn search_hits(root: &Path, pattern: &str, before: usize, after: usize) -> Vec<SearchHit> {
+    let result = search_files(
+      root.to_string_lossy().to_string(), pattern.into(), None, None, None, Some(before), Some(after), None,
+    ).unwrap();
+    let SearchResult::Hits(hits) = result else { panic!("expected ungrouped hits") };
+    hits
+  }
+
+  #[test]
+  fn hits_carry_their_context_window() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.txt", "l1\nl2\nMATCH3\nl4\nl5\nl6\nl7\nMATCH8\n");
+    let hits = search_hits(dir.path(), "MATCH", 1, 2);
+    let windows: Vec<(usize, usize, Vec<String>)> = hits.into_iter().map(|h| (h.line_number, h.context_start, h.context)).collect();
+    assert_eq!(windows, [
+      (3, 2, vec!["l2".to_string(), "MATCH3".into(), "l4".into(), "l5".into()]),
+      (8, 7, vec!["l7".to_string(), "MATCH8".into()]),
+    ]);
+  }
+
+  #[test]
+  fn overlapping_before_context_is_not_repeated_but_match_line_is() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.txt", "l1\nMATCH2\nMATCH3\nl4\nl5\n");
+    let hits = search_hits(dir.path(), "MATCH", 2, 1);
+    let windows: Vec<(usize, Vec<String>)> = hits.into_iter().map(|h| (h.context_start, h.context)).collect();
+    // The second hit's "before" lines were already shown with the first hit;
+    // its own match line is repeated even though the first hit showed it too
+    assert_eq!(windows, [
+      (1, vec!["l1".to_string(), "MATCH2".into(), "MATCH3".into()]),
+      (3, vec!["MATCH3".to_string(), "l4".into()]),
+    ]);
+  }
+
+  /* ---------- find_duplicate_files ---------- */
+
+  #[test]
+  fn identical_files_form_one_group() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    write_file(root, "a.txt", "same bytes");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    write_file(&root.join("sub"), "copy.txt", "same bytes");
+    write_file(root, "other.txt", "diff bytes");   // same size, different content
+    write_file(root, "empty1.txt", "");
+    write_file(root, "empty2.txt", "");
+    write_file(root, ".gitignore", "ignored.txt\n");
+    write_file(root, "ignored.txt", "same bytes");
+
+    let groups = find_duplicate_files(root.to_string_lossy().to_string()).unwrap();
+    assert_eq!(groups.len(), 1);
+    let mut paths: Vec<String> = groups[0].paths.iter().map(|p| rel_slash_path(root, Path::new(p))).collect();
+    paths.sort();
+    assert_eq!(paths, ["a.txt", "sub/copy.txt"]);
+    assert_eq!(groups[0].size, 10);
+    assert_eq!(groups[0].hash, sha256_hex(b"same bytes"));
+  }
+
+  /* ---------- html_to_text ---------- */
+
+  const ARTICLE: &str = r#"<html><head><title>T</title><style>p { color: red }</style></head>
+    <body><h1>Heading</h1><p>First   <b>bold</b>
+    words.</p><script>var hidden = 1;</script><ul><li>one</li><li>two</li></ul></body></html>"#;
+
+  #[test]
+  fn html_to_text_skips_script_and_style() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "a.html", ARTICLE);
+    let text = html_to_text(path, None).unwrap();
+    // <head> (title included) is skipped along with script and style
+    assert_eq!(text, "Heading\nFirst bold words.\none\ntwo");
+  }
+
+  #[test]
+  fn html_to_text_can_flatten_to_one_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "a.html", ARTICLE);
+    assert_eq!(html_to_text(path, Some(false)).unwrap(), "Heading First bold words. one two");
+  }
+
+  /* ---------- extract_html_blocks_from_url ---------- */
+
+  #[tokio::test]
+  async fn html_blocks_are_extracted_from_a_served_page() {
+    let (base, seen) = mock_server(|req| match req.path.as_str() {
+      "/cards" => http_response("200 OK", &[("Content-Type", "text/html")],
+        r#"<div class="card" id="a"><h2>A</h2><p>alpha</p></div><div class="card" id="b"><p>beta</p></div>"#),
+      _ => http_response("404 Not Found", &[], ""),
+    });
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({ "itemSelector": ".card", "descSelector": "p" })).unwrap();
+    let units = extract_html_blocks_from_url(format!("{}/cards", base), config, None, None, None, None, None).await.unwrap();
+    let got: Vec<(&str, &str)> = units.iter().map(|u| (u.id.as_str(), u.body.as_str())).collect();
+    assert_eq!(got, [("a", "alpha"), ("b", "beta")]);
+    assert_eq!(seen.lock().unwrap()[0].header("user-agent"), Some(BROWSER_USER_AGENT));
+
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({ "itemSelector": ".card" })).unwrap();
+    let missing = extract_html_blocks_from_url(format!("{}/gone", base), config, None, None, None, None, None).await;
+    assert!(matches!(missing, Err(CommandError::Network(m)) if m.ends_with("returned 404 Not Found")));
+  }
+
+  /* ---------- named ranges ---------- */
+
+  fn named_range_workbook(dir: &Path, names: &[(&str, &str)]) -> PathBuf {
+    let path = dir.join("named.xlsx");
+    write_xlsx(&path, &[
+      ("Data", vec![
+        vec!["title row"],
+        vec!["", "Code", "Price", "Note"],
+        vec!["", "p1", "10", "x"],
+        vec!["", "p2", "20", "y"],
+        vec!["", "p3", "30", "z"],
+      ]),
+      ("Blank", vec![]),
+    ], names);
+    path
+  }
+
+  #[test]
+  fn list_named_ranges_splits_sheet_and_reference() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = named_range_workbook(dir.path(), &[("PriceTable", "Data!$B$2:$C$4"), ("Quoted", "'Data'!$A$1")]);
+    let names = list_named_ranges(path.to_string_lossy().to_string()).unwrap();
+    let got: Vec<(&str, Option<&str>, &str)> = names.iter().map(|n| (n.name.as_str(), n.sheet.as_deref(), n.range.as_str())).collect();
+    assert_eq!(got, [("PriceTable", Some("Data"), "$B$2:$C$4"), ("Quoted", Some("Data"), "$A$1")]);
+  }
+
+  #[test]
+  fn named_range_limits_extraction_to_its_area() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = named_range_workbook(dir.path(), &[
+      ("PriceTable", "Data!$B$2:$C$4"),
+      ("Reversed", "Data!$C$4:$B$2"),
+    ]);
+    for name in ["PriceTable", "Reversed"] {
+      let units = excel_units(&path, excel_config(name, "Code", &["Price"]));
+      assert_eq!(ids(&units), ["p1", "p2"], "{}", name);
+      // Row indices stay relative to the area, whose header is its first row
+      assert_eq!(units[0].meta.as_ref().unwrap()["rowIndex"], 1);
+    }
+  }
+
+  #[test]
+  fn whole_sheet_named_range_is_clamped_to_used_cells() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = named_range_workbook(dir.path(), &[("Everything", "Data!$A$2:$XFD$1048576")]);
+    assert_eq!(ids(&excel_units(&path, excel_config("Everything", "Code", &["Note"]))), ["p1", "p2", "p3"]);
+  }
+
+  #[test]
+  fn preview_excel_accepts_a_named_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = named_range_workbook(dir.path(), &[("PriceTable", "Data!$B$2:$C$4")]);
+    let table = preview_excel(path.to_string_lossy().to_string(), "PriceTable".into(), None, None).unwrap();
+    assert_eq!(table.columns, ["Code", "Price"]);
+    let codes: Vec<&str> = table.rows.iter().map(|r| r["Code"].as_str()).collect();
+    assert_eq!(codes, ["p1", "p2"]);
+  }
+
+  #[test]
+  fn named_range_on_an_empty_sheet_is_an_error_not_a_panic() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = named_range_workbook(dir.path(), &[("Nothing", "Blank!$A$1:$C$3"), ("Outside", "Data!$H$20:$J$30")]);
+    for name in ["Nothing", "Outside"] {
+      let result = extract_excel_units(path.to_string_lossy().to_string(), excel_config(name, "Code", &["Price"]));
+      assert!(matches!(result, Err(CommandError::Parse(_))), "{}", name);
+    }
+  }
+
+  #[test]
+  fn cell_refs_with_absurd_columns_are_rejected() {
+    assert_eq!(parse_cell_ref("$B$12"), Some((11, 1)));
+    assert_eq!(parse_cell_ref("XFD1048576"), Some((1_048_575, 16_383)));
+    assert_eq!(parse_cell_ref("ZZZZZZZZZZZZZZZZ1"), None);
+    assert_eq!(parse_cell_ref("A0"), None);
+    assert_eq!(parse_cell_ref("A99999999999"), None);
+  }
+
+  /* ---------- fallbackItemSelector ---------- */
+
+  fn selector_config(fallback: &str) -> HtmlConfig {
+    serde_json::from_value(serde_json::json!({ "itemSelector": ".card", "fallbackItemSelector": fallback })).unwrap()
+  }
+
+  #[test]
+  fn fallback_selector_is_used_when_primary_misses() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", r#"<article class="post" id="p1">new markup</article>"#);
+    let units = extract_html_blocks(path, selector_config("article.post")).unwrap();
+    assert_eq!(ids(&units), ["p1"]);
+    assert_eq!(units[0].meta.as_ref().unwrap()["selector"], "article.post");
+  }
+
+  #[test]
+  fn primary_selector_wins_when_it_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html",
+      r#"<div class="card" id="c1">old markup</div><article class="post" id="p1">new markup</article>"#);
+    let units = extract_html_blocks(path, selector_config("article.post")).unwrap();
+    assert_eq!(ids(&units), ["c1"]);
+    assert_eq!(units[0].meta.as_ref().unwrap()["selector"], ".card");
+  }
+
+  #[test]
+  fn invalid_fallback_selector_is_a_config_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", "<p>x</p>");
+    let result = extract_html_blocks(path, selector_config("[["));
+    assert!(matches!(result, Err(CommandError::Config(m)) if m == "Invalid fallbackItemSelector"));
+  }
+
+  /* ---------- absolute links ---------- */
+
+  #[test]
+  fn relative_href_and_src_become_absolute() {
+    let mut doc = Html::parse_document(r#"<a href="/a">x</a><img src="img/b.png"><a href="https://y.org/c">y</a><a title="/t">z</a>"#);
+    absolutize_links(&mut doc, &reqwest::Url::parse("https://x.com/p").unwrap());
+    let html = doc.root_element().html();
+    assert!(html.contains(r#"href="https://x.com/a""#), "{}", html);
+    assert!(html.contains(r#"src="https://x.com/img/b.png""#), "{}", html);
+    assert!(html.contains(r#"href="https://y.org/c""#), "{}", html);
+    assert!(html.contains(r#"title="/t""#), "{}", html);
+  }
+
+  #[tokio::test]
+  async fn url_extraction_absolutizes_links_only_when_asked() {
+    let (base, _) = mock_server(|_| http_response("200 OK", &[], r#"<div class="u" id="a"><a href="/a">link</a></div>"#));
+    let config = || -> HtmlConfig { serde_json::from_value(serde_json::json!({ "itemSelector": ".u", "includeHtml": true })).unwrap() };
+    let url = format!("{}/docs/page", base);
+
+    let units = extract_html_blocks_from_url(url.clone(), config(), None, None, Some(true), None, None).await.unwrap();
+    assert_eq!(units[0].meta.as_ref().unwrap()["html"], format!(r#"<a href="{}/a">link</a>"#, base));
+    let units = extract_html_blocks_from_url(url, config(), None, None, None, None, None).await.unwrap();
+    assert_eq!(units[0].meta.as_ref().unwrap()["html"], r#"<a href="/a">link</a>"#);
+  }
+
+  /* ---------- save_units_with_manifest ---------- */
+
+  #[test]
+  fn second_save_skips_unchanged_units() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out").to_string_lossy().to_string();
+    let units = || vec![unit("a", "alpha"), unit("b", "beta")];
+
+    let first = save_units_with_manifest(out.clone(), units(), None).unwrap();
+    assert_eq!((first.written, first.skipped), (2, 0));
+    let second = save_units_with_manifest(out.clone(), units(), None).unwrap();
+    assert_eq!((second.written, second.skipped), (0, 2));
+
+    // A changed body is rewritten in place; a new id gets its own file
+    let third = save_units_with_manifest(out.clone(), vec![unit("a", "alpha v2"), unit("b", "beta"), unit("c", "gamma")], None).unwrap();
+    assert_eq!((third.written, third.skipped), (2, 1));
+    assert_eq!(fs::read_to_string(Path::new(&out).join("a.md")).unwrap(), "alpha v2");
+
+    let manifest: Value = serde_json::from_str(&fs::read_to_string(Path::new(&out).join(MANIFEST_FILENAME)).unwrap()).unwrap();
+    assert_eq!(manifest["c"], serde_json::json!({ "file": "c.md", "hash": sha256_hex(b"gamma") }));
+  }
+
+  #[test]
+  fn missing_file_is_written_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().to_string_lossy().to_string();
+    save_units_with_manifest(out.clone(), vec![unit("a", "alpha")], Some("txt".into())).unwrap();
+    fs::remove_file(dir.path().join("a.txt")).unwrap();
+    let again = save_units_with_manifest(out, vec![unit("a", "alpha")], Some("txt".into())).unwrap();
+    assert_eq!((again.written, again.skipped), (1, 0));
+    assert!(dir.path().join("a.txt").is_file());
+  }
+
+  /* ---------- expandTabs ---------- */
+
+  #[test]
+  fn tabs_expand_to_the_requested_width() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "t.txt", "a\tb\n\t\tc\n");
+    let read = |expand: Option<usize>| {
+      read_ascii_files(vec![path.clone()], None, None, expand, None, None, None).unwrap().remove(0).value
+    };
+    assert_eq!(read(Some(4)), "a    b\n        c\n");
+    // Tabs are kept by default
+    assert_eq!(read(None), "a\tb\n\t\tc\n");
+  }
+
+  /* ---------- extract_auto ---------- */
+
+  #[test]
+  fn extract_auto_routes_by_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let xlsx = dir.path().join("book.XLSX");
+    write_xlsx(&xlsx, &[("S", vec![vec!["ID", "Text"], vec!["x1", "from excel"]])], &[]);
+    let units = extract_auto(xlsx.to_string_lossy().to_string(),
+      serde_json::json!({ "sheet": "S", "idColumn": "ID", "descriptionColumns": ["Text"] })).unwrap();
+    assert_eq!(units[0].body, "from excel");
+
+    let html = write_file(dir.path(), "page.htm", r#"<p class="u" id="h1">from html</p>"#);
+    let units = extract_auto(html, serde_json::json!({ "itemSelector": ".u" })).unwrap();
+    assert_eq!((units[0].id.as_str(), units[0].body.as_str()), ("h1", "from html"));
+
+    let jsonl = write_file(dir.path(), "rows.jsonl", "{\"k\":\"j1\",\"v\":\"from json\"}\n");
+    let units = extract_auto(jsonl, serde_json::json!({ "idField": "k", "bodyFields": ["v"] })).unwrap();
+    assert_eq!((units[0].id.as_str(), units[0].body.as_str()), ("j1", "from json"));
+  }
+
+  #[test]
+  fn extract_auto_text_uses_regex_with_a_delimiter_else_paragraphs() {
+    let dir = tempfile::tempdir().unwrap();
+    let md = write_file(dir.path(), "doc.md", SECTIONS);
+    assert_eq!(extract_auto(md.clone(), serde_json::json!({ "delimiter": "(?m)^## " })).unwrap().len(), 3);
+    // Unknown extension without a delimiter: blank-line paragraphs
+    let other = write_file(dir.path(), "notes.weird", PARAGRAPHS);
+    assert_eq!(extract_auto(other, serde_json::json!({ "minLen": 5 })).unwrap().len(), 2);
+  }
+
+  #[test]
+  fn extract_auto_reports_a_bad_sub_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let html = write_file(dir.path(), "page.html", "<p>x</p>");
+    assert!(matches!(extract_auto(html, serde_json::json!({})), Err(CommandError::Config(m)) if m.contains("HTML")));
+  }
+
+  /* ---------- count_regex_blocks ---------- */
+
+  #[test]
+  fn count_matches_extraction_length() {
+    let dir = tempfile::tempdir().unwrap();
+    // The blank preamble before the first delimiter is an empty block
+    let path = write_file(dir.path(), "doc.md", "\n\n## A\nalpha\n## B\nb\n## C\ngamma gamma\n## D\ndelta\n");
+    let configs: Vec<Value> = vec![
+      serde_json::json!({ "delimiter": "(?m)^## " }),
+      serde_json::json!({ "delimiter": "(?m)^## ", "minBodyLen": 8 }),
+      serde_json::json!({ "delimiter": "(?m)^## ", "maxUnits": 2 }),
+      serde_json::json!({ "delimiter": "(?m)^## ", "keepEmpty": true, "normalizeWhitespace": true }),
+      serde_json::json!({ "delimiter": "nomatch" }),
+    ];
+    for c in configs {
+      let count = count_regex_blocks(path.clone(), serde_json::from_value(c.clone()).unwrap()).unwrap();
+      let units = extract_regex_blocks(path.clone(), serde_json::from_value(c.clone()).unwrap()).unwrap();
+      assert_eq!(count, units.len(), "{}", c);
+    }
+
+    let invalid: Vec<Value> = vec![
+      serde_json::json!({ "delimiter": "(?m)^## ", "idCapture": "(" }),
+      serde_json::json!({ "delimiter": "(?m)^## ", "collectCaptures": "[" }),
+      serde_json::json!({ "delimiter": "(?m)^## ", "idMode": "random" }),
+      serde_json::json!({ "delimiter": "(?m)^## ", "idCase": "title" }),
+    ];
+    for c in invalid {
+      let count = count_regex_blocks(path.clone(), serde_json::from_value(c.clone()).unwrap());
+      let units = extract_regex_blocks(path.clone(), serde_json::from_value(c.clone()).unwrap());
+      assert!(matches!(count, Err(CommandError::Config(_))), "{}", c);
+      assert!(matches!(units, Err(CommandError::Config(_))), "{}", c);
+    }
+  }
+
+  /* ---------- keepEmpty placeholders ---------- */
+
+  fn skipped_reason(u: &PromptUnit) -> Option<&str> {
+    u.meta.as_ref().and_then(|m| m.get("skippedReason")).and_then(Value::as_str)
+  }
+
+  #[test]
+  fn excel_keep_empty_emits_placeholders_and_preview_counts_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = mixed_rows_workbook(dir.path());
+    let config = || {
+      let mut c = excel_config("Rows", "ID", &["Text"]);
+      c.keep_empty = Some(true);
+      c
+    };
+    let units = excel_units(&path, config());
+    assert_eq!(ids(&units), ["a", "c", "d", "e"]);
+    assert_eq!((units[1].body.as_str(), skipped_reason(&units[1])), ("", Some("empty_body")));
+    assert_eq!(skipped_reason(&units[0]), None);
+
+    let p = preview_excel_units(path.to_string_lossy().to_string(), config()).unwrap();
+    assert_eq!((p.would_extract, p.skipped_empty_body, p.skipped_empty_id), (units.len(), 0, 1));
+  }
+
+  #[test]
+  fn regex_and_html_keep_empty_emit_placeholders() {
+    let dir = tempfile::tempdir().unwrap();
+    // Blocks start at the delimiter, so only the blank preamble is empty
+    let path = write_file(dir.path(), "doc.txt", "  \n@@one\n@@two");
+    let mut config = regex_config("@@");
+    config.keep_empty = Some(true);
+    let units = extract_regex_blocks(path, config).unwrap();
+    let got: Vec<(&str, Option<&str>)> = units.iter().map(|u| (u.body.as_str(), skipped_reason(u))).collect();
+    assert_eq!(got, [("", Some("empty_body")), ("@@one", None), ("@@two", None)]);
+
+    let path = write_file(dir.path(), "page.html", r#"<p class="u" id="a">x</p><p class="u" id="b">  </p>"#);
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({ "itemSelector": ".u", "keepEmpty": true })).unwrap();
+    let units = extract_html_blocks(path.clone(), config).unwrap();
+    assert_eq!(ids(&units), ["a", "b"]);
+    assert_eq!(skipped_reason(&units[1]), Some("empty_body"));
+
+    let config: HtmlConfig = serde_json::from_value(serde_json::json!({ "itemSelector": ".u" })).unwrap();
+    assert_eq!(ids(&extract_html_blocks(path, config).unwrap()), ["a"]);
+  }
+
+  /* ---------- fuzzyColumns ---------- */
+
+  fn product_workbook(dir: &Path) -> PathBuf {
+    let path = dir.join("products.xlsx");
+    write_xlsx(&path, &[("P", vec![
+      vec!["Product ID ", "Long  Description", "Notes"],
+      vec!["p-1", "a widget", "n"],
+    ])], &[]);
+    path
+  }
+
+  #[test]
+  fn fuzzy_columns_match_across_spacing_case_and_punctuation() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = product_workbook(dir.path());
+    let mut config = excel_config("P", "productid", &["long_description"]);
+    config.fuzzy_columns = Some(true);
+    let units = excel_units(&path, config);
+    assert_eq!((units[0].id.as_str(), units[0].body.as_str()), ("p-1", "a widget"));
+    // The matched header names are reported back exactly as stored
+    let meta = units[0].meta.as_ref().unwrap();
+    assert_eq!(meta["idColumn"], "Product ID ");
+    assert_eq!(meta["descriptionColumns"], serde_json::json!(["Long  Description"]));
+  }
+
+  #[test]
+  fn exact_matching_is_the_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = product_workbook(dir.path()).to_string_lossy().to_string();
+    let result = extract_excel_units(path, excel_config("P", "productid", &["Notes"]));
+    assert!(matches!(result, Err(CommandError::Config(m)) if m == "ID column not found: productid"));
+  }
+
+  /* ---------- proxy ---------- */
+
+  #[tokio::test]
+  async fn requests_go_through_the_given_proxy() {
+    // The mock plays the proxy: it receives absolute-form request targets
+    let (proxy, seen) = mock_server(page_and_api);
+    let table = fetch_api_table_from_url(
+      "http://api.invalid/extract".into(), "http://site.invalid/page".into(), None, None, None, Some(proxy), None,
+    ).await.unwrap();
+    assert_eq!(table.rows.len(), 1);
+    let targets: Vec<String> = seen.lock().unwrap().iter().map(|r| r.path.clone()).collect();
+    assert_eq!(targets, ["http://site.invalid/page", "http://api.invalid/extract"]);
+  }
+
+  #[tokio::test]
+  async fn invalid_proxy_url_is_a_config_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", "<p>hi</p>");
+    let result = fetch_api_table("http://api.invalid/extract".into(), path, None, Some("not a url".into()), None, None).await;
+    assert!(matches!(result, Err(CommandError::Config(m)) if m.starts_with("Invalid proxy URL not a url")));
+    // Blank means "use the environment", not an error
+    assert!(with_proxy(reqwest::Client::builder(), Some("  ")).is_ok());
+  }
+
+  /* ---------- cookies and redirects ---------- */
+
+  // /start sets a session cookie and redirects to /page; POST /old is moved to /extract
+  fn redirecting_site(req: &SeenRequest) -> Vec<u8> {
+    match (req.method.as_str(), req.path.as_str()) {
+      ("GET", "/start") => http_response("302 Found", &[("Location", "/page"), ("Set-Cookie", "sid=abc; Path=/")], ""),
+      ("POST", "/old") => http_response("307 Temporary Redirect", &[("Location", "/extract")], ""),
+      _ => page_and_api(req),
+    }
+  }
+
+  #[tokio::test]
+  async fn redirects_carry_set_cookie_and_seeded_cookies() {
+    let (base, seen) = mock_server(redirecting_site);
+    let cookies = HashMap::from([("consent".to_string(), "yes".to_string())]);
+    let table = fetch_api_table_from_url(
+      format!("{}/old", base), format!("{}/start", base), None, None, None, None, Some(cookies),
+    ).await.unwrap();
+    assert_eq!(table.rows.len(), 1);
+
+    let seen = seen.lock().unwrap();
+    let paths: Vec<(&str, &str)> = seen.iter().map(|r| (r.method.as_str(), r.path.as_str())).collect();
+    assert_eq!(paths, [("GET", "/start"), ("GET", "/page"), ("POST", "/old"), ("POST", "/extract")]);
+    assert_eq!(seen[0].header("cookie"), Some("consent=yes"));
+    let follow_up: Vec<&str> = seen[1].header("cookie").unwrap().split("; ").collect();
+    assert!(follow_up.contains(&"sid=abc") && follow_up.contains(&"consent=yes"), "{:?}", follow_up);
+    // The 307 keeps the method and body
+    assert_eq!(seen[3].body, seen[2].body);
+  }
+
+  /* ---------- split_file_to_dir ---------- */
+
+  #[test]
+  fn split_respects_chunk_bytes_except_for_unbreakable_paragraphs() {
+    let dir = tempfile::tempdir().unwrap();
+    let long = "x".repeat(60);
+    let text = format!("{}\n\n{}\n\n{}\n\n{}\n\n{}\n", "a".repeat(20), "b".repeat(20), long, "c".repeat(10), "d".repeat(10));
+    let path = write_file(dir.path(), "book.txt", &text);
+    let out = dir.path().join("chunks");
+    let written = split_file_to_dir(path, out.to_string_lossy().to_string(), 45, None, None).unwrap();
+
+    let names: Vec<String> = written.iter().map(|p| rel_slash_path(&out, Path::new(p))).collect();
+    assert_eq!(names, ["book-001.md", "book-002.md", "book-003.md"]);
+    let chunks: Vec<String> = written.iter().map(|p| fs::read_to_string(p).unwrap()).collect();
+    assert_eq!(chunks[0], format!("{}\n\n{}", "a".repeat(20), "b".repeat(20)));
+    assert_eq!(chunks[1], long);   // 60 bytes on its own: it cannot be split
+    assert_eq!(chunks[2], format!("{}\n\n{}", "c".repeat(10), "d".repeat(10)));
+  }
+
+  #[test]
+  fn split_rejects_a_zero_chunk_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "book.txt", "a");
+    let result = split_file_to_dir(path, dir.path().to_string_lossy().to_string(), 0, None, None);
+    assert!(matches!(result, Err(CommandError::Config(_))));
+  }
+
+  /* ---------- sortBy ---------- */
+
+  fn child_names(node: &FileNode) -> Vec<&str> {
+    node.children.iter().flatten().map(|c| c.name.as_str()).collect()
+  }
+
+  #[test]
+  fn files_sort_by_mtime_or_size_with_dirs_first() {
+    let app = tauri::test::mock_app();
+    app.manage(ScanCache::default());
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("zdir")).unwrap();
+    write_file(&root.join("zdir"), "inner.txt", "x");
+    let now = SystemTime::now();
+    for (name, size, age_secs) in [("a.txt", 3, 300), ("b.txt", 1, 100), ("c.txt", 2, 200)] {
+      let p = write_file(root, name, "x".repeat(size));
+      File::options().write(true).open(p).unwrap().set_modified(now - Duration::from_secs(age_secs)).unwrap();
+    }
+    let path = root.to_string_lossy().to_string();
+    let scan = |sort: &str| scan_dir(app.state(), path.clone(), None, None, Some(sort.into()), None).unwrap();
+
+    assert_eq!(child_names(&scan("name")), ["zdir", "a.txt", "b.txt", "c.txt"]);
+    assert_eq!(child_names(&scan("mtime")), ["zdir", "b.txt", "c.txt", "a.txt"]);
+    assert_eq!(child_names(&scan("size")), ["zdir", "a.txt", "c.txt", "b.txt"]);
+    assert!(matches!(scan_dir(app.state(), path, None, None, Some("color".into()), None), Err(CommandError::Config(_))));
+  }
+
+  /* ---------- list_files ---------- */
+
+  #[test]
+  fn list_files_flattens_the_tree_in_order() {
+    let app = tauri::test::mock_app();
+    app.manage(ScanCache::default());
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    for f in ["b.txt", "a/z.md", "a/deep/y.rs", "A.txt", "c/x.txt", ".git/HEAD", "empty/.keep"] {
+      let p = root.join(f);
+      fs::create_dir_all(p.parent().unwrap()).unwrap();
+      fs::write(p, "x").unwrap();
+    }
+    // Directories first at every level, then files by name
+    let expected = ["a/deep/y.rs", "a/z.md", "c/x.txt", "empty/.keep", "A.txt", "b.txt"];
+    assert_eq!(listed(root), expected);
+    assert_eq!(scan_with(&app, root), expected);
+
+    let only_md = list_files(root.to_string_lossy().to_string(), None, Some(r"\.md$".into()), None).unwrap();
+    assert_eq!(only_md.iter().map(|p| rel_slash_path(root, Path::new(p))).collect::<Vec<_>>(), ["a/z.md"]);
+  }
+
+  /* ---------- unreadable sheets ---------- */
+
+  // Rewrites the archive at `path` without the entry `name`
+  fn drop_zip_entry(path: &Path, name: &str) {
+    let mut archive = zip::ZipArchive::new(File::open(path).unwrap()).unwrap();
+    let tmp = path.with_extension("tmp");
+    let mut out = zip::ZipWriter::new(File::create(&tmp).unwrap());
+    for i in 0..archive.len() {
+      let entry = archive.by_index_raw(i).unwrap();
+      if entry.name() != name {
+        out.raw_copy_file(entry).unwrap();
+      }
+    }
+    out.finish().unwrap();
+    fs::rename(tmp, path).unwrap();
+  }
+
+  #[test]
+  fn unreadable_sheet_is_reported_and_others_still_listed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("corrupt.xlsx");
+    write_xlsx(&path, &[
+      ("Good", vec![vec!["ID", "Text"], vec!["a", "x"]]),
+      ("Bad", vec![vec!["ID"], vec!["b"]]),
+    ], &[]);
+    // The workbook still lists "Bad", but its part is gone
+    drop_zip_entry(&path, "xl/worksheets/sheet2.xml");
+    let info = inspect_excel(path.to_string_lossy().to_string()).unwrap();
+    assert_eq!(info.sheets.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), ["Good"]);
+    assert_eq!(info.errors.len(), 1);
+    assert_eq!(info.errors[0].sheet, "Bad");
+    assert!(!info.errors[0].message.is_empty());
+  }
+
+  /* ---------- idFallbackTemplate ---------- */
+
+  #[test]
+  fn fallback_id_templates() {
+    assert_eq!(fallback_id(None, 7), "7");
+    assert_eq!(fallback_id(Some("unit-{n:04}"), 1), "unit-0001");
+    assert_eq!(fallback_id(Some("{n}/{n:02}"), 3), "3/03");
+    // Unrecognized specs are kept literally
+    assert_eq!(fallback_id(Some("x{n:4}{n"), 5), "x{n:4}{n");
+  }
+
+  #[test]
+  fn regex_blocks_without_an_id_use_the_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", "## A\nalpha\n## \nno heading id\n");
+    let mut config = regex_config(r"(?m)^## ");
+    config.id_capture = Some(r"^## (\w+)".into());
+    config.id_fallback_template = Some("unit-{n:04}".into());
+    assert_eq!(ids(&extract_regex_blocks(path, config).unwrap()), ["A", "unit-0002"]);
+  }
+
+  #[test]
+  fn excel_rows_without_an_id_use_the_template_and_preview_agrees() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = mixed_rows_workbook(dir.path());
+    let config = || {
+      let mut c = excel_config("Rows", "ID", &["Text"]);
+      c.id_fallback_template = Some("row-{n:02}".into());
+      c
+    };
+    let units = excel_units(&path, config());
+    assert_eq!(ids(&units), ["a", "row-02", "d", "e"]);
+    let p = preview_excel_units(path.to_string_lossy().to_string(), config()).unwrap();
+    assert_eq!((p.would_extract, p.skipped_empty_id), (units.len(), 0));
+  }
+
+  /* ---------- export_markdown ---------- */
+
+  #[test]
+  fn markdown_export_has_a_heading_per_unit() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().to_string_lossy().to_string();
+    let units = || vec![
+      PromptUnit { id: "a".into(), body: "alpha".into(), meta: Some(serde_json::json!({ "rowIndex": 1 })) },
+      unit("b", "beta"),
+    ];
+    let path = export_markdown(out.clone(), "Review Doc".into(), units(), None, None).unwrap();
+    assert!(path.ends_with("Review_Doc.md"), "{}", path);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "## a\n\nalpha\n\n## b\n\nbeta\n");
+
+    // Same base again gets a fresh name; meta renders as bullets under its unit
+    let path = export_markdown(out, "Review Doc".into(), units(), Some("# {id}\n{body}\n\n".into()), Some(true)).unwrap();
+    assert!(path.ends_with("Review_Doc--2.md"), "{}", path);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# a\nalpha\n\n- rowIndex: 1\n\n# b\nbeta\n");
+  }
+
+  /* ---------- extract_dir_as_unit ---------- */
+
+  #[test]
+  fn directory_becomes_one_unit_of_its_text_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("proj");
+    fs::create_dir_all(root.join("src")).unwrap();
+    let main = write_file(&root.join("src"), "main.rs", "fn main() {}");
+    let readme = write_file(&root, "README.md", "hello");
+    write_file(&root, "logo.png", [0x89, b'P', b'N', b'G', 0, 0]);
+    write_file(&root, "SECRET", "ignored");
+    let gitignore = write_file(&root, ".gitignore", "SECRET\n");
+
+    let u = extract_dir_as_unit(root.to_string_lossy().to_string(), None, Some("# {path}".into())).unwrap();
+    assert_eq!(u.id, "proj");
+    // Binary files are left out; text files follow tree order
+    assert_eq!(u.body, format!("# {}\nfn main() {{}}\n\n# {}\nSECRET\n\n\n# {}\nhello", main, gitignore, readme));
+    assert_eq!(u.meta, Some(serde_json::json!({ "filesIncluded": 3, "totalFiles": 3, "truncated": false })));
+  }
+
+  #[test]
+  fn directory_unit_reports_truncation() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.txt", "0123456789");
+    write_file(dir.path(), "b.txt", "0123456789");
+    let u = extract_dir_as_unit(dir.path().to_string_lossy().to_string(), Some(15), Some("#".into())).unwrap();
+    assert_eq!(u.meta, Some(serde_json::json!({ "filesIncluded": 1, "totalFiles": 2, "truncated": true })));
+  }
+
+  /* ---------- validate_selector ---------- */
+
+  #[test]
+  fn validate_selector_accepts_valid_and_reports_invalid() {
+    let ok = validate_selector("div.article > p:first-child".into()).unwrap();
+    assert!(ok.valid && ok.error.is_none());
+
+    let bad = validate_selector("::::".into()).unwrap();
+    assert!(!bad.valid);
+    assert!(!bad.error.unwrap().is_empty());
+
+    let empty = validate_selector("  ".into()).unwrap();
+    assert_eq!((empty.valid, empty.error.as_deref()), (false, Some("Selector is empty")));
+  }
+
+  /* ---------- collect_captures ---------- */
+
+  #[test]
+  fn collect_captures_stores_every_match_per_block() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", "## A\ncites 1.1, 2.2 and 3.3\n## B\nno citations\n");
+    let mut config = regex_config(r"(?m)^## ");
+    config.collect_captures = Some(r"(\d+\.\d+)".into());
+    let units = extract_regex_blocks(path, config).unwrap();
+
+    assert_eq!(units.len(), 2);
+    assert_eq!(units[0].meta.as_ref().unwrap()["captures"], serde_json::json!(["1.1", "2.2", "3.3"]));
+    assert_eq!(units[1].meta.as_ref().unwrap()["captures"], serde_json::json!([]));
+  }
+
+  #[test]
+  fn collect_captures_without_a_group_uses_the_whole_match() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", "## A\nsee #12 and #7\n");
+    let mut config = regex_config(r"(?m)^## ");
+    config.collect_captures = Some(r"#\d+".into());
+    let units = extract_regex_blocks(path, config).unwrap();
+    assert_eq!(units[0].meta.as_ref().unwrap()["captures"], serde_json::json!(["#12", "#7"]));
+  }
+
+  /* ---------- diff_scans ---------- */
+
+  #[test]
+  fn diff_scans_reports_the_added_and_removed_file() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "keep.txt", "k");
+    write_file(dir.path(), "sub/old.txt", "o");
+    let old = scan_tree(dir.path(), ScanOptions::default());
+    fs::remove_file(dir.path().join("sub/old.txt")).unwrap();
+    write_file(dir.path(), "sub/new.txt", "n");
+    let new = scan_tree(dir.path(), ScanOptions::default());
+
+    let diff = diff_scans(old, new).unwrap();
+    let rel = |paths: &[String]| paths.iter().map(|p| rel_slash_path(dir.path(), Path::new(p))).collect::<Vec<_>>();
+    assert_eq!(rel(&diff.added), ["sub/new.txt"]);
+    assert_eq!(rel(&diff.removed), ["sub/old.txt"]);
+    assert_eq!(diff.common, 1);
+  }
+
+  /* ---------- normalize_newlines ---------- */
+
+  #[test]
+  fn normalize_newlines_turns_crlf_and_lone_cr_into_lf() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "win.txt", "one\r\ntwo\r\nthree\rfour\n");
+    let read = |normalize| read_ascii_files(vec![path.clone()], None, None, None, normalize, None, None).unwrap().remove(0).value;
+    assert_eq!(read(Some(true)), "one\ntwo\nthree\nfour\n");
+    // Off by default: CRs pass through untouched
+    assert_eq!(read(None), "one\r\ntwo\r\nthree\rfour\n");
+  }
+
+  /* ---------- tolerant Excel extraction ---------- */
+
+  #[test]
+  fn tolerant_mode_returns_good_units_and_row_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tolerant.xlsx");
+    write_xlsx(&path, &[("Rows", vec![
+      vec!["ID", "Text"],
+      vec!["a", "alpha"],
+      vec!["b", "=1/0->#DIV/0!"],
+      vec!["c", "gamma"],
+    ])], &[]);
+    let mut config = excel_config("Rows", "ID", &["Text", "Missing"]);
+    config.tolerant = Some(true);
+    let result = extract_excel_units(path.to_string_lossy().to_string(), config).unwrap();
+
+    let ExcelUnitsResult::Tolerant(extraction) = result else { panic!("expected {{ units, errors }}") };
+    assert_eq!(ids(&extraction.units), ["a", "c"]);
+    let errors: Vec<(Option<usize>, &str)> = extraction.errors.iter().map(|e| (e.row_index, e.message.as_str())).collect();
+    assert_eq!(errors, [(None, "Description column not found: Missing"), (Some(2), "Text has error value #DIV/0!")]);
+  }
+
+  #[test]
+  fn strict_mode_still_fails_on_a_missing_column() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = mixed_rows_workbook(dir.path());
+    let result = extract_excel_units(path.to_string_lossy().to_string(), excel_config("Rows", "ID", &["Text", "Missing"]));
+    assert!(matches!(result, Err(CommandError::Config(m)) if m == "Description column not found: Missing"));
+  }
+
+  /* ---------- estimate_cost ---------- */
+
+  #[test]
+  fn estimate_cost_multiplies_tokens_by_price() {
+    // 4000 chars -> 1000 tokens, 10 chars -> 3 tokens (rounded up)
+    let texts = vec!["a".repeat(4000), "b".repeat(10)];
+    let est = estimate_cost(texts, 0.5, Some("gpt-x".into())).unwrap();
+    let per_text: Vec<usize> = est.texts.iter().map(|t| t.tokens).collect();
+    assert_eq!(per_text, [1000, 3]);
+    assert!((est.texts[0].cost - 0.5).abs() < 1e-12);
+    assert!((est.texts[1].cost - 0.0015).abs() < 1e-12);
+    assert_eq!(est.total_tokens, 1003);
+    assert!((est.total_cost - 0.5015).abs() < 1e-12);
+    assert_eq!(est.model.as_deref(), Some("gpt-x"));
+  }
+
+  #[test]
+  fn estimate_cost_rejects_negative_or_nan_price() {
+    assert!(matches!(estimate_cost(vec![], -1.0, None), Err(CommandError::Config(_))));
+    assert!(matches!(estimate_cost(vec![], f64::NAN, None), Err(CommandError::Config(_))));
+  }
+
+  /* ---------- extract_html_tables ---------- */
+
+  const TWO_TABLES: &str = "<html><body>\
+    <table><thead><tr><th>Name</th><th>Role</th></tr></thead>\
+    <tbody><tr><td>Ada</td><td>Engineer</td></tr><tr><td colspan=\"2\">Bob</td></tr></tbody></table>\
+    <table><tr><td>k</td><td>v</td></tr><tr><td>x</td><td>1</td></tr></table>\
+    </body></html>";
+
+  #[test]
+  fn html_table_uses_thead_as_columns_and_pads_colspan() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "people.html", TWO_TABLES);
+    let tables = extract_html_tables(path, None).unwrap();
+    assert_eq!(tables.len(), 2);
+
+    let people = &tables[0];
+    assert_eq!(people.columns, ["Name", "Role"]);
+    assert_eq!(people.rows.len(), 2);
+    assert_eq!((people.rows[0]["Name"].as_str(), people.rows[0]["Role"].as_str()), ("Ada", "Engineer"));
+    assert_eq!((people.rows[1]["Name"].as_str(), people.rows[1]["Role"].as_str()), ("Bob", ""));
+  }
+
+  #[test]
+  fn html_table_index_selects_one_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "people.html", TWO_TABLES);
+    let tables = extract_html_tables(path.clone(), Some(1)).unwrap();
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].columns, ["k", "v"]);
+    assert_eq!(tables[0].rows[0]["v"], "1");
+
+    let out_of_range = extract_html_tables(path, Some(2));
+    assert!(matches!(out_of_range, Err(CommandError::Config(m)) if m == "Table index 2 out of range (2 tables)"));
+  }
+
+  /* ---------- bounded concurrency ---------- */
+
+  #[test]
+  fn bounded_map_never_exceeds_the_worker_limit() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let (in_flight, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let items: Vec<usize> = (0..24).collect();
+    let out = bounded_map(&items, 3, |&n| {
+      let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+      peak.fetch_max(now, Ordering::SeqCst);
+      std::thread::sleep(Duration::from_millis(5));
+      in_flight.fetch_sub(1, Ordering::SeqCst);
+      n * 2
+    });
+    assert_eq!(out, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    let peak = peak.load(Ordering::SeqCst);
+    assert!((1..=3).contains(&peak), "peak concurrency {}", peak);
+  }
+
+  #[test]
+  fn bounded_map_with_one_worker_is_sequential() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let (in_flight, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    bounded_map(&[1, 2, 3, 4], 0, |_| {
+      peak.fetch_max(in_flight.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+      std::thread::sleep(Duration::from_millis(2));
+      in_flight.fetch_sub(1, Ordering::SeqCst);
+    });
+    assert_eq!(peak.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn read_ascii_files_keeps_order_under_a_concurrency_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<String> = (0..10).map(|i| write_file(dir.path(), &format!("f{}.txt", i), i.to_string())).collect();
+    let read = read_ascii_files(paths, None, None, None, None, Some(2), None).unwrap();
+    let values: Vec<&str> = read.iter().map(|f| f.value.as_str()).collect();
+    assert_eq!(values, ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]);
+  }
+
+  /* ---------- read_head_tail ---------- */
+
+  fn numbered_lines(n: usize) -> String {
+    (1..=n).map(|i| format!("line {}\n", i)).collect()
+  }
+
+  #[test]
+  fn head_tail_keeps_both_ends_and_marks_the_gap() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "big.log", numbered_lines(100));
+    let v = read_head_tail(path, 3, 2).unwrap();
+    assert_eq!(v.value, "line 1\nline 2\nline 3\n... (95 lines omitted) ...\nline 99\nline 100");
+  }
+
+  #[test]
+  fn head_tail_without_a_gap_returns_every_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "small.log", numbered_lines(4));
+    assert_eq!(read_head_tail(path.clone(), 2, 2).unwrap().value, "line 1\nline 2\nline 3\nline 4");
+    // Counts far beyond the file size are fine
+    assert_eq!(read_head_tail(path, usize::MAX, usize::MAX).unwrap().value, "line 1\nline 2\nline 3\nline 4");
+  }
+
+  /* ---------- detect_excel_header ---------- */
+
+  #[test]
+  fn detect_header_counts_leading_blank_rows() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("blank-first.xlsx");
+    write_xlsx(&path, &[("Data", vec![
+      vec!["", ""],
+      vec!["ID", "Text"],
+      vec!["a", "alpha"],
+    ])], &[]);
+    let header = detect_excel_header(path.to_string_lossy().to_string(), "Data".into(), None).unwrap();
+    assert_eq!(header.header_row, 1);
+    assert_eq!(header.columns, ["ID", "Text"]);
+  }
+
+  /* ---------- read_ascii_files recurse_dirs ---------- */
+
+  #[test]
+  fn recurse_dirs_reads_a_directory_like_scan_dir_lists_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("proj");
+    write_file(&root, ".gitignore", "*.log\n");
+    write_file(&root, "a.txt", "alpha");
+    write_file(&root, "sub/b.md", "beta");
+    write_file(&root, "debug.log", "ignored");
+    let single = write_file(dir.path(), "single.txt", "solo");
+
+    let paths = vec![root.to_string_lossy().to_string(), single];
+    let read = read_ascii_files(paths.clone(), None, None, None, None, None, Some(true)).unwrap();
+    let got: Vec<(String, &str)> = read.iter().map(|f| (rel_slash_path(dir.path(), Path::new(&f.file_path)), f.value.as_str())).collect();
+    // Tree order (directories first), then the next path as given
+    assert_eq!(got, [
+      ("proj/sub/b.md".to_string(), "beta"),
+      ("proj/.gitignore".to_string(), "*.log\n"),
+      ("proj/a.txt".to_string(), "alpha"),
+      ("single.txt".to_string(), "solo"),
+    ]);
+    // Without the flag a directory is skipped as before
+    assert_eq!(read_ascii_files(paths, None, None, None, None, None, None).unwrap().len(), 1);
+  }
+
+  /* ---------- download_url_to_file ---------- */
+
+  #[tokio::test]
+  async fn download_writes_the_served_body_named_after_the_url() {
+    let (base, seen) = mock_server(|_| http_response("200 OK", &[("Content-Type", "text/html")], "<h1>Archived</h1>"));
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("archive").to_string_lossy().to_string();
+    let url = format!("{}/docs/guide.html", base);
+
+    let first = download_url_to_file(url.clone(), out.clone(), None, None, None, None, None).await.unwrap();
+    let second = download_url_to_file(url, out.clone(), None, None, None, None, None).await.unwrap();
+    assert_eq!(rel_slash_path(Path::new(&out), Path::new(&first)), "guide.html");
+    assert_ne!(first, second);
+    assert_eq!(fs::read_to_string(&first).unwrap(), "<h1>Archived</h1>");
+    assert_eq!(fs::read_to_string(&second).unwrap(), "<h1>Archived</h1>");
+    assert_eq!(seen.lock().unwrap()[0].header("user-agent"), Some(BROWSER_USER_AGENT));
+  }
+
+  #[tokio::test]
+  async fn download_uses_the_given_base_and_extension() {
+    let (base, _) = mock_server(|_| http_response("200 OK", &[], "plain"));
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().to_string_lossy().to_string();
+    let path = download_url_to_file(format!("{}/", base), out, Some("snapshot".into()), Some("txt".into()), None, None, None)
+      .await.unwrap();
+    assert_eq!(Path::new(&path).file_name().unwrap(), "snapshot.txt");
+  }
+
+  /* ---------- search grouped by directory ---------- */
+
+  #[test]
+  fn grouped_search_rolls_counts_up_to_the_root() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "top.txt", "todo\n");
+    write_file(dir.path(), "src/a.rs", "todo one\ntodo two\n");
+    write_file(dir.path(), "src/deep/b.rs", "todo\n");
+    write_file(dir.path(), "docs/c.md", "nothing here\n");
+
+    let result = search_files(
+      dir.path().to_string_lossy().to_string(), "todo".into(), None, None, None, None, None, Some(true),
+    ).unwrap();
+    let SearchResult::Grouped(grouped) = result else { panic!("expected grouped hits") };
+    assert_eq!(grouped.hits.len(), 4);
+    let mut counts: Vec<(String, usize)> = grouped.by_directory.iter()
+      .map(|(d, n)| (rel_slash_path(dir.path(), Path::new(d)), *n))
+      .collect();
+    counts.sort();
+    assert_eq!(counts, [("".to_string(), 4), ("src".to_string(), 3), ("src/deep".to_string(), 1)]);
+  }
+
+  /* ---------- header scan limit ---------- */
+
+  fn assert_header_not_found<T>(result: Result<T, CommandError>, limit: usize) {
+    let expected = format!("No header row found in the first {} rows; raise headerScanLimit if the header is further down", limit);
+    match result {
+      Err(CommandError::Parse(m)) => assert_eq!(m, expected),
+      Err(other) => panic!("expected a Parse error, got {:?}", other),
+      Ok(_) => panic!("expected a Parse error"),
+    }
+  }
+
+  #[test]
+  fn header_beyond_the_scan_limit_is_a_clear_error() {
+    // .xlsx ranges start at the first used cell, so build the blank rows directly
+    let mut range: calamine::Range<DataType> = calamine::Range::new((0, 0), (5, 1));
+    range.set_value((4, 0), DataType::String("ID".into()));
+    range.set_value((4, 1), DataType::String("Text".into()));
+
+    assert_header_not_found(detect_header_row(&range, Some(3)), 3);
+    let (idx, header) = detect_header_row(&range, Some(5)).unwrap();
+    assert_eq!((idx, header), (4, vec!["ID".to_string(), "Text".to_string()]));
+    assert_eq!(detect_header_row(&range, None).unwrap().0, 4);
+  }
+
+  #[test]
+  fn every_excel_command_reports_a_missing_header_the_same_way() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("empty.xlsx");
+    write_xlsx(&path, &[("Data", vec![])], &[]);
+    let path = path.to_string_lossy().to_string();
+
+    let limit = DEFAULT_HEADER_SCAN_LIMIT;
+    assert_header_not_found(detect_excel_header(path.clone(), "Data".into(), None), limit);
+    assert_header_not_found(preview_excel(path.clone(), "Data".into(), None, None), limit);
+    assert_header_not_found(sample_excel_rows(path.clone(), "Data".into(), 3, Some(1), None), limit);
+    assert_header_not_found(extract_excel_units(path.clone(), excel_config("Data", "ID", &["Text"])), limit);
+    assert_header_not_found(preview_excel(path, "Data".into(), None, Some(7)), 7);
+  }
+
+  /* ---------- merge_tables ---------- */
+
+  #[test]
+  fn merge_tables_unions_columns_and_fills_gaps() {
+    let a = table(&["id", "name"], &[&[("id", "1"), ("name", "one")]]);
+    let b = table(&["id", "price"], &[&[("id", "2"), ("price", "9.5")], &[("id", "3")]]);
+    let merged = merge_tables(vec![a, b]).unwrap();
+
+    assert_eq!(merged.columns, ["id", "name", "price"]);
+    let rows: Vec<Vec<&str>> = merged.rows.iter()
+      .map(|r| merged.columns.iter().map(|c| r[c].as_str()).collect())
+      .collect();
+    assert_eq!(rows, [vec!["1", "one", ""], vec!["2", "", "9.5"], vec!["3", "", ""]]);
+  }
+
+  /* ---------- idCase ---------- */
+
+  #[test]
+  fn id_case_lower_and_upper_apply_after_id_resolution() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", "## AbC\nfirst\n## xY\nsecond\n");
+    let run = |case: Option<&str>| {
+      let mut config = regex_config(r"(?m)^## ");
+      config.id_capture = Some(r"^## (\w+)".into());
+      config.id_case = case.map(str::to_string);
+      extract_regex_blocks(path.clone(), config).unwrap().into_iter().map(|u| u.id).collect::<Vec<_>>()
+    };
+    assert_eq!(run(Some("lower")), ["abc", "xy"]);
+    assert_eq!(run(Some("upper")), ["ABC", "XY"]);
+    assert_eq!(run(None), ["AbC", "xY"]);
+    assert_eq!(run(Some("none")), ["AbC", "xY"]);
+  }
+
+  #[test]
+  fn id_case_covers_excel_json_and_html_extractors() {
+    let dir = tempfile::tempdir().unwrap();
+    let xlsx = dir.path().join("ids.xlsx");
+    write_xlsx(&xlsx, &[("Rows", vec![vec!["ID", "Text"], vec!["AbC", "alpha"]])], &[]);
+    let mut excel = excel_config("Rows", "ID", &["Text"]);
+    excel.id_case = Some("lower".into());
+    assert_eq!(ids(&excel_units(&xlsx, excel)), ["abc"]);
+
+    let json = write_file(dir.path(), "ids.json", r#"[{"id":"AbC","title":"t"}]"#);
+    let mut config = json_config(None);
+    config.id_case = Some("lower".into());
+    assert_eq!(ids(&extract_json_units(json, config).unwrap()), ["abc"]);
+
+    let html = write_file(dir.path(), "ids.html", r#"<div class="card" id="AbC">card</div>"#);
+    let mut config = selector_config("none");
+    config.id_case = Some("lower".into());
+    assert_eq!(ids(&extract_html_blocks(html, config).unwrap()), ["abc"]);
+  }
+
+  #[test]
+  fn unknown_id_case_is_a_config_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", "## A\nbody\n");
+    let mut config = regex_config(r"(?m)^## ");
+    config.id_case = Some("title".into());
+    assert!(matches!(extract_regex_blocks(path, config), Err(CommandError::Config(m)) if m == "Unknown idCase: title"));
+  }
+
+  /* ---------- flag_oversized_units ---------- */
+
+  #[test]
+  fn oversized_units_are_flagged_against_max_tokens() {
+    let units = vec![unit("small", "a few words"), unit("big", &"x".repeat(4000))];
+    let report = flag_oversized_units(units, 500, Some("ctx-8k".into())).unwrap();
+    let flags: Vec<(&str, usize, bool)> = report.units.iter().map(|u| (u.id.as_str(), u.tokens, u.over_limit)).collect();
+    assert_eq!(flags, [("small", 3, false), ("big", 1000, true)]);
+    assert_eq!(report.over_limit_count, 1);
+    assert_eq!((report.max_tokens, report.model.as_deref()), (500, Some("ctx-8k")));
+  }
+
+  #[test]
+  fn a_unit_exactly_at_the_limit_is_not_over() {
+    let report = flag_oversized_units(vec![unit("edge", &"y".repeat(40))], 10, None).unwrap();
+    assert!(!report.units[0].over_limit);
+  }
+
+  /* ---------- meta.sourcePath ---------- */
+
+  #[test]
+  fn file_extractors_record_the_source_path_on_every_unit() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = |units: &[PromptUnit]| -> Vec<String> {
+      units.iter().map(|u| u.meta.as_ref().unwrap()["sourcePath"].as_str().unwrap().to_string()).collect()
+    };
+
+    let md = write_file(dir.path(), "doc.md", "## A\nalpha\n## B\nbeta\n");
+    assert_eq!(source(&extract_regex_blocks(md.clone(), regex_config(r"(?m)^## ")).unwrap()), [md.clone(), md]);
+
+    let html = write_file(dir.path(), "page.html", r#"<div class="card" id="c1">one</div><div class="card" id="c2">two</div>"#);
+    assert_eq!(source(&extract_html_blocks(html.clone(), selector_config("none")).unwrap()), [html.clone(), html]);
+
+    let txt = write_file(dir.path(), "notes.txt", "one\n\ntwo\n");
+    assert_eq!(source(&extract_paragraphs(txt.clone(), None).unwrap()), [txt.clone(), txt]);
+  }
+
+  /* ---------- watch_dir core logic ---------- */
+
+  #[test]
+  fn change_batches_are_debounced_until_quiet() {
+    let (tx, rx) = mpsc::channel::<PathBuf>();
+    let sender = std::thread::spawn(move || {
+      tx.send(PathBuf::from("/r/b.txt")).unwrap();
+      tx.send(PathBuf::from("/r/a.txt")).unwrap();
+      std::thread::sleep(Duration::from_millis(50));
+      tx.send(PathBuf::from("/r/b.txt")).unwrap();
+      std::thread::sleep(Duration::from_millis(600));
+      tx.send(PathBuf::from("/r/c.txt")).unwrap();
+    });
+    let quiet = Duration::from_millis(200);
+    // Changes within the quiet window merge into one sorted, de-duplicated batch
+    assert_eq!(next_change_batch(&rx, quiet).unwrap(), ["/r/a.txt", "/r/b.txt"]);
+    assert_eq!(next_change_batch(&rx, quiet).unwrap(), ["/r/c.txt"]);
+    sender.join().unwrap();
+    // The watcher (sender) is gone
+    assert!(next_change_batch(&rx, quiet).is_none());
+  }
+
+  #[test]
+  fn watch_filter_follows_scan_ignore_rules_including_negations() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = fs::canonicalize(dir.path()).unwrap();
+    write_file(&root, ".gitignore", "node_modules/\n!node_modules/keep-me/\n*.log\n");
+    write_file(&root, "node_modules/keep-me/index.js", "x");
+    write_file(&root, "node_modules/other/index.js", "x");
+    let gi = load_root_gitignore(&root);
+    let ignored = |rel: &str| watch_path_ignored(&root, gi.as_ref(), &root.join(rel));
+
+    assert!(!ignored("src/main.rs"));
+    assert!(!ignored("node_modules/keep-me"));
+    assert!(!ignored("node_modules/keep-me/index.js"));
+    assert!(!ignored("node_modules/keep-me/new-file.js"));
+    assert!(ignored("node_modules/other/index.js"));
+    assert!(ignored("node_modules"));
+    assert!(ignored("debug.log"));
+    assert!(ignored(".git/index"));
+    // A path outside the root is dropped instead of panicking or matching
+    assert!(watch_path_ignored(&root, gi.as_ref(), Path::new("/somewhere/else.txt")));
+  }
+
+  /* ---------- batch globalMaxUnits ---------- */
+
+  #[test]
+  fn global_cap_truncates_configs_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = two_sheet_workbook(dir.path()).to_string_lossy().to_string();
+    let configs = || vec![excel_config("Items", "ID", &["Text"]), excel_config("Notes", "Key", &["Note"])];
+
+    let Ok(ExcelBatchResult::Capped(batch)) = extract_excel_units_batch(path.clone(), configs(), Some(1)) else {
+      panic!("expected a capped batch");
+    };
+    let per_config: Vec<Vec<String>> = batch.results.into_iter()
+      .map(|r| r.into_units().into_iter().map(|u| u.id).collect())
+      .collect();
+    assert_eq!(per_config, [vec!["i1".to_string()], vec![]]);
+    assert_eq!(batch.truncated, [1, 1]);
+
+    // A cap above the combined count keeps everything
+    let Ok(ExcelBatchResult::Capped(batch)) = extract_excel_units_batch(path, configs(), Some(10)) else {
+      panic!("expected a capped batch");
+    };
+    assert_eq!(batch.truncated, [0, 0]);
+    assert_eq!(batch.results.into_iter().map(|r| r.into_units().len()).sum::<usize>(), 3);
+  }
+
+  /* ---------- tracing ---------- */
+
+  // Collects formatted events so tests can assert on what a command logged
+  #[derive(Clone, Default)]
+  struct CapturedLog(std::sync::Arc<Mutex<Vec<u8>>>);
+
+  impl std::io::Write for CapturedLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.0.lock().unwrap().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+  }
+
+  impl CapturedLog {
+    fn subscriber(&self) -> impl tracing::Subscriber + Send + Sync {
+      let writer = self.clone();
+      tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .finish()
+    }
+
+    fn text(&self) -> String {
+      String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+  }
+
+  #[test]
+  fn extraction_logs_its_span_and_unit_count() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "doc.md", "## A\nalpha\n## B\nbeta\n");
+    let log = CapturedLog::default();
+    let units = tracing::subscriber::with_default(log.subscriber(), || {
+      extract_regex_blocks(path.clone(), regex_config(r"(?m)^## ")).unwrap()
+    });
+    assert_eq!(units.len(), 2);
+
+    let text = log.text();
+    assert!(text.contains("extract_regex_blocks"), "{}", text);
+    assert!(text.contains(&format!("path={}", path)), "{}", text);
+    assert!(text.contains("extracted units units=2"), "{}", text);
+    // FmtSpan::CLOSE reports the span's timing when the command returns
+    assert!(text.contains("close time.busy="), "{}", text);
+  }
+
+  #[test]
+  fn failing_command_logs_the_error() {
+    let log = CapturedLog::default();
+    let result = tracing::subscriber::with_default(log.subscriber(), || {
+      extract_regex_blocks("/no/such/file.md".into(), regex_config("x"))
+    });
+    assert!(result.is_err());
+    assert!(log.text().contains("ERROR"), "{}", log.text());
+  }
+
+  #[tokio::test]
+  async fn network_commands_log_urls_and_status_at_debug() {
+    let (base, _) = mock_server(page_and_api);
+    let log = CapturedLog::default();
+    let _guard = tracing::subscriber::set_default(log.subscriber());
+    fetch_api_table_from_url(format!("{}/extract", base), format!("{}/page", base), None, None, None, None, None)
+      .await.unwrap();
+
+    let text = log.text();
+    assert!(text.contains(&format!("GET url={}/page status=200 OK", base)), "{}", text);
+    assert!(text.contains(&format!("POST url={}/extract status=200 OK", base)), "{}", text);
+  }
+
+  /* ---------- suggest_delimiters ---------- */
+
+  #[test]
+  fn markdown_headings_are_suggested_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let doc = "# Title\nintro\n## One\n1. step\n2. step\n## Two\ntext\n\n---\n\n## Three\nend\n";
+    let path = write_file(dir.path(), "guide.md", doc);
+    let suggestions = suggest_delimiters(path.clone()).unwrap();
+    let ranked: Vec<(&str, usize)> = suggestions.iter().map(|s| (s.label.as_str(), s.blocks)).collect();
+    // Paragraphs tie with numbered items and rank after the more specific splitter
+    assert_eq!(ranked, [("Markdown headings", 4), ("Numbered items", 3), ("Blank-line paragraphs", 3), ("Horizontal rules", 2)]);
+
+    // The suggested pattern drops straight into a RegexConfig
+    let units = extract_regex_blocks(path, regex_config(&suggestions[0].delimiter)).unwrap();
+    assert_eq!(units.len(), 4);
+  }
+
+  #[test]
+  fn plain_prose_only_offers_paragraphs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "notes.txt", "first thought\n\nsecond thought\n");
+    let suggestions = suggest_delimiters(path).unwrap();
+    let labels: Vec<&str> = suggestions.iter().map(|s| s.label.as_str()).collect();
+    assert_eq!(labels, ["Blank-line paragraphs"]);
+  }
+
+  /* ---------- payloadTemplate ---------- */
+
+  #[tokio::test]
+  async fn payload_template_is_posted_with_content_injected() {
+    let (base, seen) = mock_server(page_and_api);
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", "<p>hi</p>");
+    let template = serde_json::json!({
+      "content": "__CONTENT__",
+      "options": { "lang": "en", "depth": 2, "notes": ["source: __CONTENT__"] },
+    });
+    fetch_api_table(format!("{}/extract", base), path, None, None, Some(template), None).await.unwrap();
+
+    let posted: Value = serde_json::from_str(&seen.lock().unwrap()[0].body).unwrap();
+    assert_eq!(posted, serde_json::json!({
+      "content": "<p>hi</p>",
+      "options": { "lang": "en", "depth": 2, "notes": ["source: <p>hi</p>"] },
+    }));
+  }
+
+  #[test]
+  fn default_payload_is_a_data_key() {
+    assert_eq!(api_payload(None, "x"), serde_json::json!({ "data": "x" }));
+  }
+
+  /* ---------- sampling ---------- */
+
+  #[test]
+  fn sample_lines_is_deterministic_for_a_seed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "big.log", numbered_lines(200));
+    let pick = |seed| sample_lines(path.clone(), 5, seed).unwrap()
+      .into_iter().map(|l| (l.line_number, l.line)).collect::<Vec<_>>();
+
+    let first = pick(Some(42));
+    assert_eq!(first, pick(Some(42)));
+    assert_ne!(first, pick(Some(7)));
+    assert_eq!(first.len(), 5);
+    // In file order, and each line matches its number
+    assert!(first.windows(2).all(|w| w[0].0 < w[1].0));
+    assert!(first.iter().all(|(n, line)| *line == format!("line {}", n)));
+  }
+
+  #[test]
+  fn sample_larger_than_the_source_returns_everything() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "small.log", numbered_lines(3));
+    let all: Vec<usize> = sample_lines(path.clone(), usize::MAX, Some(1)).unwrap().iter().map(|l| l.line_number).collect();
+    assert_eq!(all, [1, 2, 3]);
+    assert!(sample_lines(path, 0, Some(1)).unwrap().is_empty());
+  }
+
+  #[test]
+  fn sample_excel_rows_is_deterministic_for_a_seed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rows.xlsx");
+    let ids: Vec<String> = (1..=50).map(|i| format!("r{}", i)).collect();
+    let mut rows: Vec<Vec<&str>> = vec![vec!["ID", "Text"]];
+    rows.extend(ids.iter().map(|id| vec![id.as_str(), "text"]));
+    write_xlsx(&path, &[("Data", rows)], &[]);
+    let path = path.to_string_lossy().to_string();
+
+    let pick = |seed| {
+      let t = sample_excel_rows(path.clone(), "Data".into(), 4, seed, None).unwrap();
+      assert_eq!(t.columns, ["ID", "Text"]);
+      t.rows.iter().map(|r| r["ID"].clone()).collect::<Vec<_>>()
+    };
+    let first = pick(Some(9));
+    assert_eq!(first.len(), 4);
+    assert_eq!(first, pick(Some(9)));
+    assert_ne!(first, pick(Some(10)));
+    // The header row is never sampled
+    assert!(!first.contains(&"ID".to_string()));
+  }
+
+  /* ---------- dedupe_units normalize ---------- */
+
+  #[test]
+  fn normalized_dedupe_merges_whitespace_variants_and_keeps_the_raw_body() {
+    let units = || vec![
+      unit("a", "foo  bar"),
+      unit("b", "foo bar"),
+      unit("c", "  foo\n\tbar \n"),
+      unit("d", "foo baz"),
+    ];
+    let kept = dedupe_units(units(), Some(true)).unwrap();
+    let got: Vec<(&str, &str)> = kept.iter().map(|u| (u.id.as_str(), u.body.as_str())).collect();
+    assert_eq!(got, [("a", "foo  bar"), ("d", "foo baz")]);
+
+    // Exact comparison by default
+    assert_eq!(ids(&dedupe_units(units(), None).unwrap()), ["a", "b", "c", "d"]);
+  }
+
+  /* ---------- meta.domIndex ---------- */
+
+  #[test]
+  fn dom_index_is_the_match_position_with_gaps_for_skipped_items() {
+    let dir = tempfile::tempdir().unwrap();
+    let html = r#"<div class="card" id="a">one</div><div class="card" id="b">two</div><div class="card" id="c"> </div><div class="card" id="d">four</div>"#;
+    let path = write_file(dir.path(), "cards.html", html);
+    let units = extract_html_blocks(path, selector_config("none")).unwrap();
+    let positions: Vec<(&str, u64)> = units.iter().map(|u| (u.id.as_str(), u.meta.as_ref().unwrap()["domIndex"].as_u64().unwrap())).collect();
+    // The empty card is skipped but keeps its index
+    assert_eq!(positions, [("a", 0), ("b", 1), ("d", 3)]);
+  }
+
+  /* ---------- labeled Excel bodies ---------- */
+
+  #[test]
+  fn labeled_bodies_prefix_each_part_with_its_header() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("labeled.xlsx");
+    write_xlsx(&path, &[("Items", vec![
+      vec!["ID", "Summary", "Description"],
+      vec!["a", "Short", "Longer text"],
+      vec!["b", "", "Only description"],
+    ])], &[]);
+    let mut config = excel_config("Items", "ID", &["Summary", "Description"]);
+    config.labeled = Some(true);
+    let bodies: Vec<String> = excel_units(&path, config).into_iter().map(|u| u.body).collect();
+    assert_eq!(bodies, ["Summary: Short\nDescription: Longer text", "Description: Only description"]);
+
+    let plain: Vec<String> = excel_units(&path, excel_config("Items", "ID", &["Summary", "Description"]))
+      .into_iter().map(|u| u.body).collect();
+    assert_eq!(plain, ["Short\nLonger text", "Only description"]);
+  }
+
+  /* ---------- ping_endpoint ---------- */
+
+  #[tokio::test]
+  async fn ping_falls_back_to_get_when_head_is_rejected() {
+    let (base, seen) = mock_server(|req| match req.method.as_str() {
+      "HEAD" => http_response("405 Method Not Allowed", &[], ""),
+      _ => http_response("200 OK", &[], "ok"),
+    });
+    let status = ping_endpoint(format!("{}/health", base), None, None).await.unwrap();
+    assert!(status.reachable);
+    assert_eq!(status.status, Some(200));
+    let methods: Vec<String> = seen.lock().unwrap().iter().map(|r| r.method.clone()).collect();
+    assert_eq!(methods, ["HEAD", "GET"]);
+  }
+
+  #[tokio::test]
+  async fn ping_counts_any_http_status_as_reachable() {
+    let (base, seen) = mock_server(|_| http_response("404 Not Found", &[], ""));
+    let status = ping_endpoint(format!("{}/missing", base), None, None).await.unwrap();
+    assert_eq!((status.reachable, status.status), (true, Some(404)));
+    assert_eq!(seen.lock().unwrap().len(), 1);
+  }
+
+  #[tokio::test]
+  async fn ping_reports_unreachable_servers() {
+    // Nothing listens on a port we just released
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let refused = ping_endpoint(format!("http://127.0.0.1:{}/", port), Some(2000), None).await.unwrap();
+    assert_eq!((refused.reachable, refused.status), (false, None));
+
+    // A server that accepts but never answers hits the timeout
+    let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = silent.local_addr().unwrap();
+    std::thread::spawn(move || {
+      let _held: Vec<_> = silent.incoming().take(2).collect();
+      std::thread::sleep(Duration::from_secs(5));
+    });
+    let timed_out = ping_endpoint(format!("http://{}/", addr), Some(200), None).await.unwrap();
+    assert_eq!((timed_out.reachable, timed_out.status), (false, None));
+
+    let invalid = ping_endpoint("not a url".into(), None, None).await;
+    assert!(matches!(invalid, Err(CommandError::Config(m)) if m.starts_with("Invalid endpoint URL not a url")));
   }
 
-  Ok(units)
-}
+  /* ---------- extract_markdown_docs ---------- */
 
-/* ---------- HTML (CSS) blocks — best practice: parse DOM, not regex ---------- */
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct HtmlConfig {
-  item_selector: String,
-  id_selector: Option<String>,
-  id_attr: Option<String>,       // defaults to "id"
-  desc_selector: Option<String>,
-}
+  const TWO_DOCS: &str = "---\nid: intro\ntitle: Getting started\ntags: [a, b]\n---\nWelcome text.\n\n---\n\nStill intro after a rule.\n\n---\nid: 42\n---\nSecond doc.\n";
 
-#[tauri::command]
-fn extract_html_blocks(path: String, config: HtmlConfig) -> Result<Vec<PromptUnit>, String> {
-  let data = std::fs::read(&path).map_err(|e| e.to_string())?;
-  let text = String::from_utf8_lossy(&data).into_owned();
+  #[test]
+  fn markdown_docs_split_on_front_matter() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "docs.md", TWO_DOCS);
+    let units = extract_markdown_docs(path.clone(), None).unwrap();
 
-  let doc = Html::parse_document(&text);
-  let item_sel = Selector::parse(&config.item_selector)
-    .map_err(|_| "Invalid itemSelector".to_string())?;
+    let got: Vec<(&str, &str)> = units.iter().map(|u| (u.id.as_str(), u.body.as_str())).collect();
+    assert_eq!(got, [("intro", "Welcome text.\n\n---\n\nStill intro after a rule."), ("42", "Second doc.")]);
+    assert_eq!(units[0].meta, Some(serde_json::json!({
+      "id": "intro", "title": "Getting started", "tags": ["a", "b"], "sourcePath": path,
+    })));
+  }
 
-  let id_sel = match &config.id_selector {
-    Some(s) if !s.trim().is_empty() => Some(Selector::parse(s).map_err(|_| "Invalid idSelector".to_string())?),
-    _ => None
-  };
-  let desc_sel = match &config.desc_selector {
-    Some(s) if !s.trim().is_empty() => Some(Selector::parse(s).map_err(|_| "Invalid descSelector".to_string())?),
-    _ => None
-  };
-  let id_attr = config.id_attr.as_deref().unwrap_or("id");
+  #[test]
+  fn markdown_docs_use_the_configured_id_field_or_a_fallback() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "docs.md", TWO_DOCS);
+    let units = extract_markdown_docs(path, Some("title".into())).unwrap();
+    assert_eq!(ids(&units), ["Getting started", "2"]);
+  }
 
-  let mut units: Vec<PromptUnit> = Vec::new();
+  #[test]
+  fn key_value_line_between_rules_in_a_body_is_not_front_matter() {
+    let dir = tempfile::tempdir().unwrap();
+    let text = "---\nid: setup\n---\nInstall the agent.\n---\nNote: run as root\n---\nThen restart.\n";
+    let path = write_file(dir.path(), "docs.md", text);
+    let units = extract_markdown_docs(path, None).unwrap();
+    assert_eq!(ids(&units), ["setup"]);
+    assert_eq!(units[0].body, "Install the agent.\n---\nNote: run as root\n---\nThen restart.");
+  }
 
-  for (i, el) in doc.select(&item_sel).enumerate() {
-    // resolve id
-    let id = if let Some(sel) = &id_sel {
-      if let Some(node) = el.select(sel).next() {
-        if let Some(v) = node.value().attr(id_attr) {
-          v.to_string()
-        } else {
-          let text = node.text().collect::<String>().trim().to_string();
-          if text.is_empty() { format!("{}", i+1) } else { text }
-        }
-      } else {
-        format!("{}", i+1)
-      }
-    } else {
-      if let Some(v) = el.value().attr(id_attr) {
-        v.to_string()
-      } else {
-        format!("{}", i+1)
-      }
-    };
+  /* ---------- inspect_excel raw columns ---------- */
 
-    // resolve description text
-    let body = if let Some(dsel) = &desc_sel {
-      let mut buf = String::new();
-      for n in el.select(dsel) {
-        let t = n.text().collect::<String>();
-        if !t.trim().is_empty() {
-          if !buf.is_empty() { buf.push('\n'); }
-          buf.push_str(t.trim());
-        }
-      }
-      if buf.is_empty() {
-        // fallback to full item text
-        el.text().collect::<String>().trim().to_string()
-      } else { buf }
-    } else {
-      el.text().collect::<String>().trim().to_string()
-    };
+  #[test]
+  fn inspect_excel_keeps_raw_headers_alongside_trimmed_ones() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("spaced.xlsx");
+    write_xlsx(&path, &[("Items", vec![
+      vec!["  Product ID ", "", " ", "Notes"],
+      vec!["p1", "x", "y", "n"],
+    ])], &[]);
+    let info = inspect_excel(path.to_string_lossy().to_string()).unwrap();
+    let sheet = &info.sheets[0];
+    assert_eq!(sheet.columns, ["Product ID", "col2", "col3", "Notes"]);
+    assert_eq!(sheet.raw_columns, ["  Product ID ", "", " ", "Notes"]);
 
-    if body.is_empty() { continue; }
-    units.push(PromptUnit { id, body, meta: None });
+    // Positions line up, so "#N" picked from the inspector resolves to the same column
+    let config = excel_config("Items", "#0", &["#3"]);
+    let units = excel_units(&path, config);
+    assert_eq!((units[0].id.as_str(), units[0].body.as_str()), ("p1", "n"));
   }
 
-  Ok(units)
-}
+  /* ---------- export_chat_jsonl ---------- */
 
-// ADD this new command (async)
-#[tauri::command]
-async fn extract_api_units(
-  endpoint: String,
-  path: String,
-  which: String,                       // "items" | "notes"
-  headers: Option<HashMap<String, String>>,
-) -> Result<Vec<PromptUnit>, String> {
-  // 1) Read the selected file
-  let data = std::fs::read(&path).map_err(|e| e.to_string())?;
-  let html_text = String::from_utf8_lossy(&data).into_owned();
+  #[test]
+  fn chat_jsonl_has_one_messages_line_per_unit() {
+    let dir = tempfile::tempdir().unwrap();
+    let answered = PromptUnit { id: "q1".into(), body: "What is 2+2?".into(), meta: Some(serde_json::json!({ "answer": 4 })) };
+    let units = vec![answered, unit("q2", "Name a color.")];
+    let path = export_chat_jsonl(
+      dir.path().to_string_lossy().to_string(), "train".into(), units,
+      Some("Be brief.".into()), "[{id}] {body}".into(), Some("answer".into()),
+    ).unwrap();
+    assert!(path.ends_with("train.jsonl"));
 
-  // 2) POST to the endpoint as JSON
-  let client = reqwest::Client::builder()
-    .user_agent("rag-util/1.0")
-    .build()
-    .map_err(|e| e.to_string())?;
+    let text = fs::read_to_string(&path).unwrap();
+    let lines: Vec<Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines, [
+      serde_json::json!({ "messages": [
+        { "role": "system", "content": "Be brief." },
+        { "role": "user", "content": "[q1] What is 2+2?" },
+        { "role": "assistant", "content": "4" },
+      ] }),
+      serde_json::json!({ "messages": [
+        { "role": "system", "content": "Be brief." },
+        { "role": "user", "content": "[q2] Name a color." },
+      ] }),
+    ]);
+  }
 
-  let mut req = client
-    .post(&endpoint)
-    .header(reqwest::header::CONTENT_TYPE, "application/json");
+  #[test]
+  fn chat_jsonl_skips_a_blank_system_prompt() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = export_chat_jsonl(
+      dir.path().to_string_lossy().to_string(), "t".into(), vec![unit("a", "hi")], Some("  ".into()), "{body}".into(), None,
+    ).unwrap();
+    let line: Value = serde_json::from_str(fs::read_to_string(path).unwrap().trim_end()).unwrap();
+    assert_eq!(line, serde_json::json!({ "messages": [{ "role": "user", "content": "hi" }] }));
+  }
 
-  if let Some(h) = headers.as_ref() {
-    for (k,v) in h {
-      req = req.header(k, v);
+  /* ---------- regex block offsets ---------- */
+
+  #[test]
+  fn regex_block_offsets_slice_back_to_the_body() {
+    let dir = tempfile::tempdir().unwrap();
+    let text = "\n  preamble  \n## A\nalpha é\n\n\n## B\n  beta\n\n";
+    let path = write_file(dir.path(), "doc.md", text);
+    let units = extract_regex_blocks(path, regex_config(r"(?m)^## ")).unwrap();
+    assert_eq!(units.len(), 3);
+    for u in &units {
+      let meta = u.meta.as_ref().unwrap();
+      let (start, end) = (meta["start"].as_u64().unwrap() as usize, meta["end"].as_u64().unwrap() as usize);
+      assert_eq!(&text[start..end], u.body);
     }
+    let first = units[0].meta.as_ref().unwrap();
+    assert_eq!((first["start"].as_u64(), first["end"].as_u64()), (Some(3), Some(11)));
   }
 
-  let payload = serde_json::json!({ "html": html_text });
-  let resp = req.json(&payload).send().await.map_err(|e| e.to_string())?;
-  if !resp.status().is_success() {
-    return Err(format!("API error {} from {}", resp.status(), endpoint));
+  #[test]
+  fn normalized_bodies_keep_the_source_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let text = "## A  \none\n\n\n\ntwo\n";
+    let path = write_file(dir.path(), "doc.md", text);
+    let mut config = regex_config(r"(?m)^## ");
+    config.normalize_whitespace = Some(true);
+    let units = extract_regex_blocks(path, config).unwrap();
+    let meta = units[0].meta.as_ref().unwrap();
+    assert_eq!(units[0].body, "## A\none\n\ntwo");
+    assert_eq!(&text[meta["start"].as_u64().unwrap() as usize..meta["end"].as_u64().unwrap() as usize], "## A  \none\n\n\n\ntwo");
   }
 
-  // 3) Accept several response shapes
-  //    - array of objects
-  //    - {items:[...]} or {notes:[...]}
-  //    Each object should have {code, items_text? / notes_text?}
-  let v: Value = resp.json().await.map_err(|e| e.to_string())?;
-  let list: Vec<Value> = if let Some(arr) = v.as_array() {
-    arr.clone()
-  } else if let Some(arr) = v.get("items").and_then(|x| x.as_array()) {
-    arr.clone()
-  } else if let Some(arr) = v.get("notes").and_then(|x| x.as_array()) {
-    arr.clone()
-  } else {
-    vec![v]
-  };
-
-  let take_key = if which.to_lowercase().starts_with('n') { "notes_text" } else { "items_text" };
-  let mut out: Vec<PromptUnit> = Vec::new();
+  /* ---------- follow_iframes ---------- */
 
-  for item in list {
-    let code = item.get("code").and_then(|x| x.as_str()).unwrap_or("").trim().to_string();
-    let body = item.get(take_key).and_then(|x| x.as_str()).unwrap_or("").trim().to_string();
-    if !code.is_empty() && !body.is_empty() {
-      out.push(PromptUnit { id: code, body, meta: None });
+  // /page embeds /frame (same origin) and a cross-origin frame; /frame holds the cards
+  fn framed_site(req: &SeenRequest) -> Vec<u8> {
+    match req.path.as_str() {
+      "/page" => http_response("200 OK", &[("Content-Type", "text/html")],
+        r#"<html><body><h1>Shell</h1><iframe src="/frame"></iframe><iframe src="http://other.invalid/x"></iframe></body></html>"#),
+      "/many" => http_response("200 OK", &[("Content-Type", "text/html")],
+        (0..8).map(|i| format!(r#"<iframe src="/frame?{}"></iframe>"#, i)).collect::<String>()),
+      _ => http_response("200 OK", &[("Content-Type", "text/html")],
+        r#"<html><body><div class="card" id="f1">framed one</div><div class="card" id="f2">framed two</div></body></html>"#),
     }
   }
 
-  Ok(out)
-}
+  #[tokio::test]
+  async fn follow_iframes_extracts_from_same_origin_frames() {
+    let (base, seen) = mock_server(framed_site);
+    let url = format!("{}/page", base);
+    let without = extract_html_blocks_from_url(url.clone(), selector_config("none"), None, None, None, None, None).await.unwrap();
+    assert!(without.is_empty());
 
-fn json_to_string(v: &Value) -> String {
-  match v {
-    Value::Null => "".into(),
-    Value::Bool(b) => b.to_string(),
-    Value::Number(n) => n.to_string(),
-    Value::String(s) => s.clone(),
-    other => serde_json::to_string(other).unwrap_or_default(),
+    let units = extract_html_blocks_from_url(url, selector_config("none"), None, None, None, None, Some(true)).await.unwrap();
+    let got: Vec<(&str, &str)> = units.iter().map(|u| (u.id.as_str(), u.body.as_str())).collect();
+    assert_eq!(got, [("f1", "framed one"), ("f2", "framed two")]);
+    // The cross-origin frame is never requested
+    let paths: Vec<String> = seen.lock().unwrap().iter().map(|r| r.path.clone()).collect();
+    assert_eq!(paths, ["/page", "/page", "/frame"]);
   }
-}
 
-// Find an array of objects in common shapes: top-level array,
-// or under items/rows/data/result/notes/records, else first array-of-objects found.
-fn find_array_of_objects(v: &Value) -> Option<Vec<Map<String, Value>>> {
-  if let Some(arr) = v.as_array() {
-    let objs: Vec<_> = arr.iter().filter_map(|x| x.as_object().cloned()).collect();
-    if !objs.is_empty() { return Some(objs); }
+  #[tokio::test]
+  async fn follow_iframes_fetches_at_most_the_cap() {
+    let (base, seen) = mock_server(framed_site);
+    extract_html_blocks_from_url(format!("{}/many", base), selector_config("none"), None, None, None, None, Some(true))
+      .await.unwrap();
+    let frames = seen.lock().unwrap().iter().filter(|r| r.path.starts_with("/frame")).count();
+    assert_eq!(frames, MAX_IFRAMES);
   }
-  if let Some(obj) = v.as_object() {
-    for key in ["items","rows","data","result","notes","records"] {
-      if let Some(val) = obj.get(key) {
-        if let Some(arr) = val.as_array() {
-          let objs: Vec<_> = arr.iter().filter_map(|x| x.as_object().cloned()).collect();
-          if !objs.is_empty() { return Some(objs); }
-        }
-      }
-    }
-    for (_k, val) in obj {
-      if let Some(arr) = val.as_array() {
-        let objs: Vec<_> = arr.iter().filter_map(|x| x.as_object().cloned()).collect();
-        if !objs.is_empty() { return Some(objs); }
-      }
-    }
+
+  /* ---------- fingerprint_paths ---------- */
+
+  fn set_mtime(path: &str, secs: u64) {
+    let f = fs::File::options().write(true).open(path).unwrap();
+    f.set_modified(std::time::UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
   }
-  None
-}
 
-#[tauri::command]
-async fn fetch_api_table(endpoint: String, path: String) -> Result<ApiTable, String> {
-  let data = std::fs::read(&path).map_err(|e| e.to_string())?;
-  let html_text = String::from_utf8_lossy(&data).into_owned();
+  #[test]
+  fn fingerprint_is_stable_until_a_file_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = write_file(dir.path(), "a.txt", "alpha");
+    let b = write_file(dir.path(), "b.txt", "beta");
+    set_mtime(&a, 1_000);
+    set_mtime(&b, 1_000);
+    let fp = |paths: Vec<String>| fingerprint_paths(paths, None).unwrap();
 
-  let client = reqwest::Client::builder()
-    .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/127 Safari/537.36")
-    .build()
-    .map_err(|e| e.to_string())?;
+    let before = fp(vec![a.clone(), b.clone()]);
+    assert_eq!(before.len(), 64);
+    // Order and duplicates don't matter
+    assert_eq!(before, fp(vec![b.clone(), a.clone(), a.clone()]));
 
-  let resp = client
-    .post(&endpoint)
-    .header(reqwest::header::CONTENT_TYPE, "application/json")
-    .json(&serde_json::json!({ "data": html_text }))    // ⬅️ changed key to "data"
-    .send()
-    .await
-    .map_err(|e| e.to_string())?;
+    fs::write(&b, "beta!").unwrap();
+    set_mtime(&b, 2_000);
+    assert_ne!(before, fp(vec![a.clone(), b.clone()]));
 
-  if !resp.status().is_success() {
-    return Err(format!("API error {} from {}", resp.status(), endpoint));
+    // A deleted file changes it too, without failing
+    let with_b = fp(vec![a.clone(), b.clone()]);
+    fs::remove_file(&b).unwrap();
+    assert_ne!(with_b, fp(vec![a, b]));
   }
 
-  let v: Value = resp.json().await.map_err(|e| e.to_string())?;
-  let objs = find_array_of_objects(&v)
-    .ok_or_else(|| "No array of objects in API response".to_string())?;
+  #[test]
+  fn content_fingerprint_ignores_a_touch() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = write_file(dir.path(), "a.txt", "alpha");
+    set_mtime(&a, 1_000);
+    let by_meta = fingerprint_paths(vec![a.clone()], None).unwrap();
+    let by_bytes = fingerprint_paths(vec![a.clone()], Some(true)).unwrap();
 
-  let mut cols: BTreeSet<String> = BTreeSet::new();
-  for o in &objs { for k in o.keys() { cols.insert(k.clone()); } }
-  let columns: Vec<String> = cols.into_iter().collect();
+    set_mtime(&a, 5_000);
+    assert_ne!(by_meta, fingerprint_paths(vec![a.clone()], None).unwrap());
+    assert_eq!(by_bytes, fingerprint_paths(vec![a.clone()], Some(true)).unwrap());
 
-  let mut rows: Vec<HashMap<String, String>> = Vec::new();
-  for o in objs {
-    let mut r = HashMap::new();
-    for c in &columns {
-      let s = o.get(c).map(json_to_string).unwrap_or_default();
-      r.insert(c.clone(), s);
-    }
-    rows.push(r);
+    fs::write(&a, "alphA").unwrap();
+    assert_ne!(by_bytes, fingerprint_paths(vec![a], Some(true)).unwrap());
   }
 
-  Ok(ApiTable { columns, rows })
-}
+  /* ---------- chunk_text ---------- */
 
-// ⬇ put this helper anywhere above `run()` (e.g., with other helpers)
-fn sanitize_for_filename(input: &str) -> String {
-  // Keep alnum, dot, dash, underscore. Everything else -> underscore.
-  let mut out: String = input
-    .chars()
-    .map(|c| {
-      if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
-        c
-      } else {
-        '_'
-      }
-    })
-    .collect();
-  while out.contains("__") {
-    out = out.replace("__", "_");
+  #[test]
+  fn chunk_text_never_splits_multibyte_chars() {
+    let text = "日本語😀é文字🎉ok".to_string();
+    let chunks = chunk_text(text.clone(), 3, None).unwrap();
+    assert_eq!(chunks, ["日本語", "😀é文", "字🎉o", "k"]);
+    assert_eq!(chunks.concat(), text);
   }
-  out.trim_matches('_').to_string()
-}
 
-#[tauri::command]
-fn save_chunk_file(dir: String, base: String, ext: Option<String>, contents: String) -> Result<String, String> {
-  let dir_path = PathBuf::from(&dir);
-  // Ensure directory exists
-  create_dir_all(&dir_path).map_err(|e| format!("mkdir failed: {}", e))?;
+  #[test]
+  fn chunk_overlap_counts_chars() {
+    let chunks = chunk_text("😀😁😂🤣😃😄".into(), 4, Some(2)).unwrap();
+    assert_eq!(chunks, ["😀😁😂🤣", "😂🤣😃😄"]);
+    assert!(chunk_text("".into(), 4, None).unwrap().is_empty());
+  }
 
-  let ext_sanitized = sanitize_for_filename(ext.unwrap_or_else(|| "md".to_string()).trim_matches('.'));
-  let mut base_sanitized = sanitize_for_filename(&base);
-  if base_sanitized.is_empty() {
-    base_sanitized = "chunk".to_string();
+  #[test]
+  fn chunk_text_rejects_bad_sizes() {
+    assert!(matches!(chunk_text("x".into(), 0, None), Err(CommandError::Config(_))));
+    assert!(matches!(
+      chunk_text("x".into(), 2, Some(2)),
+      Err(CommandError::Config(m)) if m == "overlapChars (2) must be less than chunkChars (2)"
+    ));
   }
 
-  // Build unique filename: base.ext, base--2.ext, base--3.ext, ...
-  let mut attempt: usize = 1;
-  let final_path = loop {
-    let candidate = if attempt == 1 {
-      dir_path.join(format!("{}.{}", base_sanitized, ext_sanitized))
-    } else {
-      dir_path.join(format!("{}--{}.{}", base_sanitized, attempt, ext_sanitized))
-    };
-    if !candidate.exists() {
-      break candidate;
-    }
-    attempt += 1;
-    if attempt > 9999 {
-      return Err("Failed to create a unique filename (too many conflicts)".into());
+  /* ---------- fetch_api_table_from_urls ---------- */
+
+  // GET /missing is a 404; other pages name themselves; POST echoes the page as one row
+  fn section_pages(req: &SeenRequest) -> Vec<u8> {
+    match (req.method.as_str(), req.path.as_str()) {
+      ("GET", "/missing") => http_response("404 Not Found", &[], ""),
+      ("GET", path) => http_response("200 OK", &[], format!("<p>{}</p>", path)),
+      _ => {
+        let posted: Value = serde_json::from_str(&req.body).unwrap();
+        http_response("200 OK", &[("Content-Type", "application/json")], serde_json::json!([{ "page": posted["data"] }]).to_string())
+      }
     }
-  };
+  }
 
-  fs::write(&final_path, contents).map_err(|e| format!("write failed: {}", e))?;
-  Ok(final_path.to_string_lossy().to_string())
-}
+  #[tokio::test]
+  async fn multi_url_fetch_merges_rows_and_collects_errors() {
+    let (base, _) = mock_server(section_pages);
+    let urls = vec![format!("{}/one", base), format!("{}/missing", base), format!("{}/two", base)];
+    let result = fetch_api_table_from_urls(format!("{}/extract", base), urls, None, None, None, Some(2)).await.unwrap();
 
-// ASCII filter for downloaded bytes (keeps \t \n \r and printable ASCII)
-fn ascii_only_from_bytes(buf: &[u8]) -> String {
-  let mut out = String::with_capacity(buf.len());
-  for &b in buf.iter() {
-    match b {
-      9 | 10 | 13 => out.push(b as char),       // \t \n \r
-      32..=126 => out.push(b as char),          // printable ASCII
-      _ => {}
-    }
+    assert_eq!(result.table.columns, ["source_url", "page"]);
+    let rows: Vec<(String, &str)> = result.table.rows.iter()
+      .map(|r| (r["source_url"].trim_start_matches(&base).to_string(), r["page"].as_str()))
+      .collect();
+    assert_eq!(rows, [("/one".to_string(), "<p>/one</p>"), ("/two".to_string(), "<p>/two</p>")]);
+
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.errors[0].url, format!("{}/missing", base));
+    assert!(result.errors[0].message.contains("404"), "{}", result.errors[0].message);
   }
-  out
-}
 
-#[tauri::command]
-async fn fetch_api_table_from_url(endpoint: String, url: String) -> Result<ApiTable, String> {
-  // 1) Download the source URL (try to mimic a real browser)
-  let client = reqwest::Client::builder()
-    .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/127 Safari/537.36")
-    .redirect(reqwest::redirect::Policy::limited(10))
-    .build()
-    .map_err(|e| e.to_string())?;
+  /* ---------- preview_filenames ---------- */
 
-  let mut html_text = {
-    let resp = client
-      .get(&url)
-      .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
-      .header(reqwest::header::ACCEPT_LANGUAGE, "en-US,en;q=0.9")
-      .send()
-      .await
-      .map_err(|e| format!("GET {} failed: {}", url, e))?;
+  #[test]
+  fn preview_flags_ids_that_sanitize_to_the_same_name() {
+    let units = vec![unit("Review Doc", "a"), unit("Review/Doc", "b"), unit("other", "c")];
+    let planned = preview_filenames(units, None, None).unwrap();
+    let got: Vec<(&str, &str, bool)> = planned.iter().map(|p| (p.id.as_str(), p.filename.as_str(), p.collision)).collect();
+    assert_eq!(got, [
+      ("Review Doc", "Review_Doc.md", false),
+      ("Review/Doc", "Review_Doc--2.md", true),
+      ("other", "other.md", false),
+    ]);
+  }
 
-    if !resp.status().is_success() {
-      return Err(format!("GET {} returned {}", url, resp.status()));
-    }
+  #[test]
+  fn preview_counts_existing_files_in_dir_as_taken() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "notes.txt", "already here");
+    let planned = preview_filenames(
+      vec![unit("notes", "x")], Some(".txt".into()), Some(dir.path().to_string_lossy().to_string()),
+    ).unwrap();
+    assert_eq!((planned[0].filename.as_str(), planned[0].collision), ("notes--2.txt", true));
+    // Nothing was written
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+  }
 
-    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
-    String::from_utf8_lossy(&bytes).into_owned()
-  };
+  /* ---------- scan_dir relativePaths ---------- */
 
-  // 1b) eCFR-specific fallback: if we don't see the expected markers, try /current/
-  let looks_like_app_shell = !html_text.contains("flush-paragraph-2") && url.contains("ecfr.gov");
-  if looks_like_app_shell && url.contains("/on/") {
-    if let Some(current_url) = url.replace("/on/", "/current/").into() {
-      let resp2 = client
-        .get(&current_url)
-        .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
-        .header(reqwest::header::ACCEPT_LANGUAGE, "en-US,en;q=0.9")
-        .send()
-        .await
-        .map_err(|e| format!("GET {} failed: {}", current_url, e))?;
-
-      if resp2.status().is_success() {
-        let bytes2 = resp2.bytes().await.map_err(|e| e.to_string())?;
-        let html2 = String::from_utf8_lossy(&bytes2).into_owned();
-        // Only replace if the fallback actually looks better
-        if html2.contains("flush-paragraph-2") {
-          html_text = html2;
-        }
-      }
-    }
+  #[test]
+  fn relative_paths_are_root_relative_with_abs_path_kept() {
+    let app = tauri::test::mock_app();
+    app.manage(ScanCache::default());
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "src/main.rs", "fn main() {}");
+    write_file(dir.path(), "README.md", "# hi");
+    let root = dir.path().to_string_lossy().to_string();
+
+    // Scan once absolute (filling the cache), then relative from the cache
+    let absolute = scan_dir(app.state(), root.clone(), None, None, None, None).unwrap();
+    assert!(absolute.abs_path.is_none());
+    let tree = scan_dir(app.state(), root, None, None, None, Some(true)).unwrap();
+
+    assert_eq!((tree.path.as_str(), tree.abs_path.as_deref()), (".", Some(absolute.path.as_str())));
+    let src = child(&tree, "src");
+    assert_eq!(src.path, "src");
+    let main = child(src, "main.rs");
+    assert_eq!(main.path, "src/main.rs");
+    assert_eq!(fs::read_to_string(main.abs_path.as_ref().unwrap()).unwrap(), "fn main() {}");
+    assert_eq!(child(&tree, "README.md").path, "README.md");
+
+    // The cached tree itself stays absolute
+    let again = scan_dir(app.state(), dir.path().to_string_lossy().to_string(), None, None, None, None).unwrap();
+    assert_eq!(child(child(&again, "src"), "main.rs").path, dir.path().join("src/main.rs").to_string_lossy());
   }
 
-  // 2) Post the ASCII/UTF-8 text to your extraction API as { data: ... }
-  let resp = client
-    .post(&endpoint)
-    .header(reqwest::header::CONTENT_TYPE, "application/json")
-    .json(&serde_json::json!({ "data": html_text }))  // ⬅️ your FastAPI expects "data"
-    .send()
-    .await
-    .map_err(|e| format!("POST {} failed: {}", endpoint, e))?;
+  /* ---------- extract_code_blocks ---------- */
 
-  if !resp.status().is_success() {
-    return Err(format!("Extraction API error {} from {}", resp.status(), endpoint));
+  const CODE_DOC: &str = "# Demo\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\nText.\n\n~~~Python title=x\nprint('hi')\n~~~\n\n````\n```nested```\n````\n";
+
+  fn code_blocks(filter: Option<&str>) -> Vec<(String, String, u64)> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "demo.md", CODE_DOC);
+    extract_code_blocks(path, filter.map(str::to_string)).unwrap().into_iter()
+      .map(|u| {
+        let meta = u.meta.unwrap();
+        (meta["language"].as_str().unwrap().to_string(), u.body, meta["line"].as_u64().unwrap())
+      })
+      .collect()
   }
 
-  let v: Value = resp.json().await.map_err(|e| e.to_string())?;
-  let objs = find_array_of_objects(&v)
-    .ok_or_else(|| "No array of objects in extraction response".to_string())?;
+  #[test]
+  fn code_blocks_capture_language_body_and_line() {
+    assert_eq!(code_blocks(None), [
+      ("rust".to_string(), "fn main() {\n    println!(\"hi\");\n}".to_string(), 3),
+      ("Python".to_string(), "print('hi')".to_string(), 11),
+      ("".to_string(), "```nested```".to_string(), 15),
+    ]);
+  }
 
-  // Normalize to columns + rows table
-  let mut cols: BTreeSet<String> = BTreeSet::new();
-  for o in &objs { for k in o.keys() { cols.insert(k.clone()); } }
-  let columns: Vec<String> = cols.into_iter().collect();
+  #[test]
+  fn language_filter_is_case_insensitive() {
+    let python = code_blocks(Some("python"));
+    assert_eq!(python.len(), 1);
+    assert_eq!(python[0].1, "print('hi')");
+    assert_eq!(code_blocks(Some("RUST")).len(), 1);
+    assert!(code_blocks(Some("go")).is_empty());
+  }
 
-  let mut rows: Vec<HashMap<String, String>> = Vec::new();
-  for o in objs {
-    let mut r = HashMap::new();
-    for c in &columns {
-      let s = o.get(c).map(json_to_string).unwrap_or_default();
-      r.insert(c.clone(), s);
-    }
-    rows.push(r);
+  /* ---------- 429 Retry-After ---------- */
+
+  #[tokio::test]
+  async fn rate_limited_post_is_retried_after_retry_after() {
+    let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = calls.clone();
+    let (base, seen) = mock_server(move |req| {
+      if req.method == "POST" && counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+        http_response("429 Too Many Requests", &[("Retry-After", "0")], "slow down")
+      } else {
+        page_and_api(req)
+      }
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", "<p>hi</p>");
+    let table = fetch_api_table(format!("{}/extract", base), path, None, None, None, None).await.unwrap();
+    assert_eq!(table.columns, ["code", "text"]);
+    assert_eq!(seen.lock().unwrap().iter().filter(|r| r.method == "POST").count(), 2);
+  }
+
+  #[test]
+  fn retry_after_accepts_seconds_and_http_dates() {
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1_445_412_470);
+    assert_eq!(parse_retry_after(" 120 ", now), Some(Duration::from_secs(120)));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now), Some(Duration::from_secs(10)));
+    assert_eq!(parse_retry_after("Tue, 20 Oct 2015 07:28:00 GMT", now), Some(Duration::ZERO));
+    assert_eq!(parse_retry_after("soon", now), None);
   }
 
-  Ok(ApiTable { columns, rows })
-}
\ No newline at end of file
+  /* ---------- includeRaw ---------- */
+
+  #[tokio::test]
+  async fn raw_response_is_kept_only_when_requested() {
+    let (base, _) = mock_server(page_and_api);
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_file(dir.path(), "page.html", "<p>hi</p>");
+
+    let with_raw = fetch_api_table(format!("{}/extract", base), path.clone(), None, None, None, Some(true)).await.unwrap();
+    assert_eq!(with_raw.raw, Some(serde_json::json!([{ "code": "1", "text": "one" }])));
+
+    let without = fetch_api_table(format!("{}/extract", base), path, None, None, None, None).await.unwrap();
+    assert_eq!(without.raw, None);
+    assert!(serde_json::to_value(&without).unwrap().get("raw").is_none());
+  }
+}
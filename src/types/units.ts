@@ -52,4 +52,5 @@ export type UnitConfig = ExcelConfig | RegexConfig | HtmlConfig | ApiConfig;
 export interface ApiTable {
   columns: string[];
   rows: Record<string, string>[]; // flat row of column -> stringified value
+  raw?: unknown;                  // full API response, only when includeRaw was set
 }
\ No newline at end of file